@@ -1,44 +1,93 @@
-// Checks if the bit index is 1 in byte. Index starts at 0
+use std::ops::{BitAnd, BitAndAssign, BitOrAssign, Not, Shl, Shr};
+
+// Unsigned integer used to store the bits of a sieve
+pub trait Word: Copy + PartialEq
+  + BitAnd<Output = Self> + BitAndAssign + BitOrAssign + Not<Output = Self>
+  + Shl<u32, Output = Self> + Shr<u32, Output = Self> {
+
+  const BITS: u32;
+  const ZERO: Self;
+  const ONE: Self;
+  const ONES: Self;
+
+  fn count_ones(self) -> u32;
+  fn trailing_zeros(self) -> u32;
+  // Build a word from its bytes, the first one in the least significant bits
+  fn from_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_word {
+  ($type:ty) => {
+    impl Word for $type {
+      const BITS: u32 = <$type>::BITS;
+      const ZERO: Self = 0;
+      const ONE: Self = 1;
+      const ONES: Self = <$type>::MAX;
+
+      #[inline]
+      fn count_ones(self) -> u32 { <$type>::count_ones(self) }
+
+      #[inline]
+      fn trailing_zeros(self) -> u32 { <$type>::trailing_zeros(self) }
+
+      #[inline]
+      fn from_bytes(bytes: &[u8]) -> Self {
+        <$type>::from_le_bytes(bytes.try_into().unwrap())
+      }
+    }
+  };
+}
+
+impl_word!(u8);
+impl_word!(u64);
+
+// Checks if the bit index is 1 in word. Index starts at 0
 #[inline]
 #[allow(dead_code)]
-pub fn is_bit_set(byte: &u8, bit: u8) -> bool {
-  byte & (1 << bit) != 0
+pub fn is_bit_set<W: Word>(word: &W, bit: u32) -> bool {
+  *word & (W::ONE << bit) != W::ZERO
 }
 
-// Checks if the bit index is 0 in byte. Index starts at 0
+// Checks if the bit index is 0 in word. Index starts at 0
 #[inline]
-pub fn is_bit_unset(byte: &u8, bit: u8) -> bool {
-  byte & (1 << bit) == 0
+pub fn is_bit_unset<W: Word>(word: &W, bit: u32) -> bool {
+  *word & (W::ONE << bit) == W::ZERO
 }
 
-// Set the bit index in byte to 1. Index starts at 0
+// Set the bit index in word to 1. Index starts at 0
 #[inline]
 #[allow(dead_code)]
-pub fn set_bit(byte: &mut u8, bit: u8) {
-  *byte |= 1 << bit;
+pub fn set_bit<W: Word>(word: &mut W, bit: u32) {
+  *word |= W::ONE << bit;
 }
 
-// Set the bit index in byte to 0. Index starts at 0
+// Set the bit index in word to 0. Index starts at 0
 #[inline]
-pub fn unset_bit(byte: &mut u8, bit: u8) {
-  *byte &= !(1 << bit);
-} 
+pub fn unset_bit<W: Word>(word: &mut W, bit: u32) {
+  *word &= !(W::ONE << bit);
+}
 
-// Count how many 1 bits are there in byte
+// Count how many 1 bits are there in word
 #[inline]
-pub fn count_set_bits(byte: &u8) -> u8 {
-  byte.count_ones() as u8
+pub fn count_set_bits<W: Word>(word: &W) -> u32 {
+  word.count_ones()
 }
 
-// Count how many 0 bits are there in byte
+// Count how many 0 bits are there in word
 #[inline]
 #[allow(dead_code)]
-pub fn count_unset_bits(byte: &u8) -> u8 {
-  byte.count_zeros() as u8
+pub fn count_unset_bits<W: Word>(word: &W) -> u32 {
+  W::BITS - word.count_ones()
 }
 
-// Set the last (most significant) n bits in byte to 0.
+// Set the last (most significant) n bits in word to 0.
 #[inline]
-pub fn unset_last_bits(byte: &mut u8, bit: u8) {
-  *byte &= 0xff >> bit;
-}
\ No newline at end of file
+pub fn unset_last_bits<W: Word>(word: &mut W, bit: u32) {
+  if bit >= W::BITS { *word = W::ZERO } else { *word &= W::ONES >> bit }
+}
+
+// Set the first (least significant) n bits in word to 0.
+#[inline]
+pub fn unset_first_bits<W: Word>(word: &mut W, bit: u32) {
+  if bit >= W::BITS { *word = W::ZERO } else { *word &= W::ONES << bit }
+}
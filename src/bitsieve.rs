@@ -0,0 +1,450 @@
+use memmap2::Mmap;
+
+use crate::bucket::BucketSieve;
+use crate::cpu;
+use crate::try_simple_sieve;
+use crate::try_vec;
+use crate::wheel;
+use crate::Error;
+use crate::Result;
+
+// Bytes of the header of a serialized sieve: low, high, the primes of the wheel and the number of words
+const HEADER_SIZE: usize = 8 + 8 + 1 + 8;
+
+// The prime numbers between low and high (both included), stored as a bitmap in memory
+// so they can be queried repeatedly. The numbers coprime to 30 are stored with the same
+// mod 30 wheel as the sieve (each byte holds 30 numbers), and 2, 3 and 5 are kept apart
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitSieve {
+  low: usize,
+  high: usize,
+  // Multiple of 30 where the bitmap starts
+  base: usize,
+  // Bit i is set if the i-th prime of the wheel (2, 3 or 5) is in the sieve
+  wheel_primes: u8,
+  words: Words,
+}
+
+// Words of the bitmap, either owned or read in place from a memory-mapped file
+#[derive(Debug)]
+enum Words {
+  Owned(Vec<u64>),
+  // The words are in little endian, starting at a byte of the map
+  Mapped(Mmap, usize),
+}
+
+// Iterator over the numbers set in a BitSieve, in increasing order
+pub struct BitSieveIter<'a> {
+  sieve: &'a BitSieve,
+  start: usize,
+  end: usize,
+  // Position of the next prime of the wheel to check, and of the current word
+  wheel_prime: usize,
+  word: usize,
+  // Bits of the current word not returned yet
+  bits: u64,
+}
+
+impl Words {
+
+  fn len(&self) -> usize {
+    match self {
+      Words::Owned(words) => words.len(),
+      Words::Mapped(map, offset) => (map.len() - offset) / 8,
+    }
+  }
+
+  #[inline]
+  fn get(&self, i: usize) -> u64 {
+    match self {
+      Words::Owned(words) => words[i],
+      Words::Mapped(map, offset) => u64::from_le_bytes(map[offset + i * 8..offset + i * 8 + 8].try_into().unwrap()),
+    }
+  }
+
+  // Count the bits set in the first words
+  fn count_ones(&self, words: usize) -> usize {
+    match self {
+      Words::Owned(owned) => owned[..words].iter().map(|word| word.count_ones() as usize).sum(),
+      Words::Mapped(map, offset) => map[*offset..offset + words * 8].chunks_exact(8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()).count_ones() as usize)
+        .sum(),
+    }
+  }
+
+  // Get the words to modify them, copying them first if they are mapped (so the file never changes)
+  fn to_mut(&mut self) -> &mut Vec<u64> {
+    if let Words::Mapped(..) = self {
+      *self = self.clone();
+    }
+    match self {
+      Words::Owned(words) => words,
+      Words::Mapped(..) => unreachable!(),
+    }
+  }
+}
+
+impl Clone for Words {
+  fn clone(&self) -> Words {
+    Words::Owned((0..self.len()).map(|i| self.get(i)).collect())
+  }
+}
+
+impl PartialEq for Words {
+  fn eq(&self, other: &Words) -> bool {
+    self.len() == other.len() && (0..self.len()).all(|i| self.get(i) == other.get(i))
+  }
+}
+
+impl Eq for Words {}
+
+// Get the multiple of 30 where the bitmap of the numbers between low and high starts,
+// and its number of words, without allocating it
+fn layout(low: usize, high: usize) -> (usize, usize) {
+  // The bitmap starts at the same byte as the sieve, which skips 0 to 6
+  let base: usize = std::cmp::max(low, 7) / wheel::WHEEL * wheel::WHEEL;
+  let bytes: usize = if low > high || high < 7 { 0 } else { (high - base) / wheel::WHEEL + 1 };
+  (base, wheel::words::<u64>(bytes))
+}
+
+// Range and primes of the wheel read from the header of a serialized sieve
+struct Header {
+  low: usize,
+  high: usize,
+  wheel_primes: u8,
+}
+
+impl Header {
+
+  // Build the sieve of the header with its words
+  fn into_sieve(self, words: Words) -> BitSieve {
+    BitSieve { low: self.low, high: self.high, base: layout(self.low, self.high).0, wheel_primes: self.wheel_primes, words }
+  }
+}
+
+// Read the header of a serialized sieve, or None if it is not valid or the words after it
+// don't match the range. Nothing is allocated, so a forged header can't ask for a huge bitmap
+fn read_header(bytes: &[u8]) -> Option<Header> {
+
+  if bytes.len() < HEADER_SIZE { return None }
+  let read = |position: usize| u64::from_le_bytes(bytes[position..position + 8].try_into().unwrap());
+
+  let low: usize = usize::try_from(read(0)).ok()?;
+  let high: usize = usize::try_from(read(8)).ok()?;
+  let wheel_primes: u8 = bytes[16];
+  let words: usize = usize::try_from(read(17)).ok()?;
+
+  // The number of words must match the range and the bytes left
+  if wheel_primes >= 1 << wheel::WHEEL_PRIMES.len() { return None }
+  if words != layout(low, high).1 { return None }
+  if bytes.len() != HEADER_SIZE.checked_add(words.checked_mul(8)?)? { return None }
+
+  Some(Header { low, high, wheel_primes })
+}
+
+impl BitSieve {
+
+  // Sieve the numbers between low and high
+  pub fn new(low: usize, high: usize) -> BitSieve {
+    BitSieve::try_new(low, high).unwrap_or_else(|err| panic!("{err}"))
+  }
+
+  // Sieve the numbers between low and high like new, or get an error if there isn't memory
+  // for the bitmap (a byte every 30 numbers) or the primes up to the square root of high
+  pub fn try_new(low: usize, high: usize) -> Result<BitSieve> {
+
+    let mut sieve: BitSieve = BitSieve::try_empty(low, high)?;
+    if low > high { return Ok(sieve) }
+
+    for (i, &prime) in wheel::WHEEL_PRIMES.iter().enumerate() {
+      if low <= prime as usize && prime as usize <= high { sieve.wheel_primes |= 1 << i }
+    }
+
+    // The segments are aligned with the bitmap, so they are just copied one after another
+    let primes: Vec<u32> = try_simple_sieve(high.isqrt() as u32)?;
+    let cache: cpu::CacheInfo = cpu::get_cache_info();
+    let mut segments: BucketSieve<u64> = BucketSieve::new(&primes, low, high, cache.l1_size(), cache.l2_size());
+    let words: &mut Vec<u64> = sieve.words.to_mut();
+    let mut position: usize = 0;
+    while let Some((_, segment)) = segments.next_segment() {
+      words[position..position + segment.len()].copy_from_slice(segment);
+      position += segment.len();
+    }
+
+    // The sieving primes are crossed off too, so set them again
+    for &prime in primes.iter().filter(|&&prime| prime >= 7) {
+      sieve.set(prime as usize);
+    }
+
+    Ok(sieve)
+  }
+
+  // Create a sieve for the numbers between low and high, without any number set
+  pub fn empty(low: usize, high: usize) -> BitSieve {
+    BitSieve::try_empty(low, high).unwrap_or_else(|err| panic!("{err}"))
+  }
+
+  // Create an empty sieve like empty, or an error if there isn't memory for the bitmap
+  fn try_empty(low: usize, high: usize) -> Result<BitSieve> {
+
+    let (base, words): (usize, usize) = layout(low, high);
+
+    Ok(BitSieve {
+      low,
+      high,
+      base,
+      wheel_primes: 0,
+      words: Words::Owned(try_vec(0, words)?),
+    })
+  }
+
+  // Lowest number of the sieve
+  pub fn low(&self) -> usize {
+    self.low
+  }
+
+  // Greatest number of the sieve
+  pub fn high(&self) -> usize {
+    self.high
+  }
+
+  // Get the word and the bit of a number, or None if it isn't on the bitmap
+  // (it isn't coprime to 30, or the range ends below 7 and there are no words)
+  fn position(&self, num: usize) -> Option<(usize, u32)> {
+    let offset: usize = num.checked_sub(self.base)?;
+    let bit: u32 = wheel::bit_of(offset % wheel::WHEEL)?;
+    let byte: usize = offset / wheel::WHEEL;
+    if byte / 8 >= self.words.len() { return None }
+    Some((byte / 8, (byte % 8) as u32 * 8 + bit))
+  }
+
+  // Check if a number is set, or None if it is not between low and high
+  pub fn get(&self, num: usize) -> Option<bool> {
+
+    if num < self.low || num > self.high { return None }
+
+    if let Some(i) = wheel::WHEEL_PRIMES.iter().position(|&prime| prime as usize == num) {
+      return Some(self.wheel_primes & (1 << i) != 0);
+    }
+
+    Some(match self.position(num) {
+      Some((word, bit)) => self.words.get(word) & (1 << bit) != 0,
+      None => false,
+    })
+  }
+
+  // Set a number, returning if it was set before. Returns None if it is not between low
+  // and high, or if it can't be stored (the multiples of 2, 3 and 5 aren't on the bitmap)
+  pub fn set(&mut self, num: usize) -> Option<bool> {
+
+    let was_set: bool = self.get(num)?;
+
+    if let Some(i) = wheel::WHEEL_PRIMES.iter().position(|&prime| prime as usize == num) {
+      self.wheel_primes |= 1 << i;
+    }
+    else {
+      let (word, bit) = self.position(num)?;
+      self.words.to_mut()[word] |= 1 << bit;
+    }
+
+    Some(was_set)
+  }
+
+  // Clear a number, returning if it was set before. Returns None if it is not between low and high
+  pub fn clear(&mut self, num: usize) -> Option<bool> {
+
+    let was_set: bool = self.get(num)?;
+
+    if let Some(i) = wheel::WHEEL_PRIMES.iter().position(|&prime| prime as usize == num) {
+      self.wheel_primes &= !(1 << i);
+    }
+    else if let Some((word, bit)) = self.position(num) {
+      if was_set { self.words.to_mut()[word] &= !(1 << bit) }
+    }
+
+    Some(was_set)
+  }
+
+  // Count how many numbers are set
+  pub fn count(&self) -> usize {
+    self.wheel_primes.count_ones() as usize + self.words.count_ones(self.words.len())
+  }
+
+  // Count how many numbers lower than or equal to x are set
+  pub fn rank(&self, x: usize) -> usize {
+
+    if x < self.low { return 0 }
+    let x: usize = std::cmp::min(x, self.high);
+
+    let mut count: usize = wheel::WHEEL_PRIMES.iter().enumerate()
+      .filter(|&(i, &prime)| prime as usize <= x && self.wheel_primes & (1 << i) != 0)
+      .count();
+    if x < self.base || self.words.len() == 0 { return count }
+
+    // Count the full words, and the bits up to x in the last one
+    let offset: usize = x - self.base;
+    let byte: usize = offset / wheel::WHEEL;
+    let bits: u32 = (byte % 8) as u32 * 8 + wheel::residues_below(offset % wheel::WHEEL + 1);
+    count += self.words.count_ones(byte / 8);
+    if bits > 0 { count += (self.words.get(byte / 8) << (u64::BITS - bits)).count_ones() as usize }
+
+    count
+  }
+
+  // Get the k-th number set (starting at 1), or None if there are less than k numbers set
+  pub fn select(&self, k: usize) -> Option<usize> {
+
+    if k == 0 { return None }
+    let mut k: usize = k;
+
+    for (i, &prime) in wheel::WHEEL_PRIMES.iter().enumerate() {
+      if self.wheel_primes & (1 << i) != 0 {
+        if k == 1 { return Some(prime as usize) }
+        k -= 1;
+      }
+    }
+
+    for i in 0..self.words.len() {
+      let word: u64 = self.words.get(i);
+      let ones: usize = word.count_ones() as usize;
+      if k > ones {
+        k -= ones;
+        continue
+      }
+
+      // Remove the lowest k - 1 bits of the word
+      let mut word: u64 = word;
+      for _ in 1..k { word &= word - 1 }
+      return Some(wheel::number::<u64>(self.base, i, word.trailing_zeros()));
+    }
+
+    None
+  }
+
+  // Iterate over the numbers set, in increasing order
+  pub fn iter(&self) -> BitSieveIter<'_> {
+    self.range(self.low, self.high)
+  }
+
+  // Iterate over the numbers set between start and end (both included), in increasing order
+  pub fn range(&self, start: usize, end: usize) -> BitSieveIter<'_> {
+
+    let start: usize = std::cmp::max(start, self.low);
+    let end: usize = std::cmp::min(end, self.high);
+
+    // Start at the word of start, without the bits of the numbers lower than it
+    let (word, bits): (usize, u64) = if start > end || self.words.len() == 0 {
+      (self.words.len(), 0)
+    }
+    else if start < self.base {
+      (0, self.words.get(0))
+    }
+    else {
+      let offset: usize = start - self.base;
+      let byte: usize = offset / wheel::WHEEL;
+      let below: u32 = (byte % 8) as u32 * 8 + wheel::residues_below(offset % wheel::WHEEL);
+      (byte / 8, self.words.get(byte / 8) & (u64::MAX << below))
+    };
+
+    BitSieveIter {
+      sieve: self,
+      start,
+      end,
+      wheel_prime: 0,
+      word,
+      bits,
+    }
+  }
+
+  // Serialize the sieve: low, high, the primes of the wheel and the number of
+  // words in a header, followed by the words (every number in little endian)
+  pub fn to_bytes(&self) -> Vec<u8> {
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_SIZE + self.words.len() * 8);
+    bytes.extend_from_slice(&(self.low as u64).to_le_bytes());
+    bytes.extend_from_slice(&(self.high as u64).to_le_bytes());
+    bytes.push(self.wheel_primes);
+    bytes.extend_from_slice(&(self.words.len() as u64).to_le_bytes());
+    for i in 0..self.words.len() {
+      bytes.extend_from_slice(&self.words.get(i).to_le_bytes());
+    }
+
+    bytes
+  }
+
+  // Read a sieve serialized with to_bytes, or None if the bytes are not a valid sieve
+  pub fn from_bytes(bytes: &[u8]) -> Option<BitSieve> {
+    BitSieve::try_from_bytes(bytes).ok()
+  }
+
+  // Read a sieve serialized with to_bytes like from_bytes, or get
+  // Error::InvalidData if the bytes are not a valid sieve
+  pub fn try_from_bytes(bytes: &[u8]) -> Result<BitSieve> {
+
+    let header: Header = read_header(bytes).ok_or_else(|| Error::InvalidData(String::from("invalid serialized sieve")))?;
+    let words: Vec<u64> = bytes[HEADER_SIZE..].chunks_exact(8).map(|word| u64::from_le_bytes(word.try_into().unwrap())).collect();
+
+    Ok(header.into_sieve(Words::Owned(words)))
+  }
+
+  // Use a sieve serialized with to_bytes from a byte of a memory map until its end, reading
+  // the words from the map instead of copying them. None if the bytes are not a valid sieve
+  pub(crate) fn from_map(map: Mmap, offset: usize) -> Option<BitSieve> {
+
+    let header: Header = read_header(map.get(offset..)?)?;
+    Some(header.into_sieve(Words::Mapped(map, offset + HEADER_SIZE)))
+  }
+
+  // Get the memory map the words are read from, or None if they are not mapped
+  pub(crate) fn map(&self) -> Option<&Mmap> {
+    match &self.words {
+      Words::Owned(_) => None,
+      Words::Mapped(map, _) => Some(map),
+    }
+  }
+}
+
+impl Iterator for BitSieveIter<'_> {
+  type Item = usize;
+
+  fn next(&mut self) -> Option<usize> {
+
+    // The primes of the wheel go first, as they are the lowest
+    while self.wheel_prime < wheel::WHEEL_PRIMES.len() {
+      let prime: usize = wheel::WHEEL_PRIMES[self.wheel_prime] as usize;
+      self.wheel_prime += 1;
+      if self.sieve.wheel_primes & (1 << (self.wheel_prime - 1)) != 0 && self.start <= prime && prime <= self.end {
+        return Some(prime);
+      }
+    }
+
+    // Move to the next word with bits set
+    while self.bits == 0 {
+      self.word += 1;
+      if self.word >= self.sieve.words.len() { return None }
+      self.bits = self.sieve.words.get(self.word);
+    }
+
+    let bit: u32 = self.bits.trailing_zeros();
+    self.bits &= self.bits - 1;
+    let num: usize = wheel::number::<u64>(self.sieve.base, self.word, bit);
+
+    // Stop for good after the end of the range
+    if num > self.end {
+      self.bits = 0;
+      self.word = self.sieve.words.len();
+      return None;
+    }
+
+    Some(num)
+  }
+}
+
+impl<'a> IntoIterator for &'a BitSieve {
+  type Item = usize;
+  type IntoIter = BitSieveIter<'a>;
+
+  fn into_iter(self) -> BitSieveIter<'a> {
+    self.iter()
+  }
+}
@@ -0,0 +1,170 @@
+use crate::bits::Word;
+use crate::presieve;
+use crate::wheel;
+use crate::wheel::Multiple;
+
+// Sieve of the numbers coprime to 30 between low and high, one segment at a time,
+// keeping the next multiple of every prime from one segment to the next. The primes
+// greater than the segment size hit each segment at most once, so instead of going
+// through all of them in every segment, each one is kept in the bucket of the segment
+// where its next multiple falls (Oliveira e Silva's bucket sieve), and only the
+// primes in the bucket of the current segment are visited. The segments are sized for
+// L2, and the primes small enough to hit a block of L1 size many times are crossed off
+// one block at a time, so each block stays in L1 while they go through it.
+// Every prime with a multiple in the range keeps it here (16 bytes), so near 2^64 the
+// primes up to 2^32 take about 3 GB. Use reset to sieve another range with the same memory
+pub struct BucketSieve<W: Word> {
+  presieved: bool,
+  // Next multiple of the primes crossed off in every block
+  small: Vec<Multiple>,
+  // Next multiple of the primes crossed off in every segment
+  medium: Vec<Multiple>,
+  // Circular list of buckets, one for each of the next segments
+  buckets: Vec<Vec<Multiple>>,
+  sieve: Vec<W>,
+  low: usize,
+  high: usize,
+  // Bytes of the first and the last numbers in a sieve starting at 0
+  first_byte: usize,
+  last_byte: usize,
+  // The segments have 2^shift bytes (at least one word), and the blocks 2^block_shift
+  // (at least one word, at most a segment). Index of the next segment
+  shift: u32,
+  block_shift: u32,
+  segment: usize,
+}
+
+impl<W: Word> BucketSieve<W> {
+
+  // Create a sieve for the numbers between low and high without divisors in primes (so
+  // the primes themselves are unset), with segments of up to segment_bytes bytes and the
+  // small primes crossed off in blocks of up to block_bytes bytes
+  pub fn new(primes: &[u32], low: usize, high: usize, block_bytes: usize, segment_bytes: usize) -> BucketSieve<W> {
+
+    // A power of two lets the bucket of a multiple be found with a shift
+    let shift: u32 = std::cmp::max(segment_bytes, wheel::bytes_per_word::<W>()).ilog2();
+    let block_shift: u32 = std::cmp::min(std::cmp::max(block_bytes, wheel::bytes_per_word::<W>()).ilog2(), shift);
+
+    let mut sieve: BucketSieve<W> = BucketSieve {
+      presieved: false,
+      small: Vec::new(),
+      medium: Vec::new(),
+      buckets: Vec::new(),
+      sieve: Vec::new(),
+      low,
+      high,
+      first_byte: 0,
+      last_byte: 0,
+      shift,
+      block_shift,
+      segment: 0,
+    };
+    sieve.reset(primes, low, high);
+    sieve
+  }
+
+  // Start sieving the numbers between low and high without divisors in primes like new, with
+  // the same segments and blocks. The memory of the multiples and the buckets is kept, so a
+  // thread sieving many ranges only allocates it for the first one
+  pub fn reset(&mut self, primes: &[u32], low: usize, high: usize) {
+
+    // Skip 1, which isn't prime, and the primes of the wheel
+    let low: usize = std::cmp::max(low, 7);
+    let first_byte: usize = low / wheel::WHEEL;
+    let last_byte: usize = high / wheel::WHEEL;
+    let shift: u32 = self.shift;
+    let segment_size: usize = (1 << shift) * wheel::WHEEL;
+    let block_size: usize = (1 << self.block_shift) * wheel::WHEEL;
+
+    // The primes of the wheel have no multiples on the sieve, and the
+    // presieve ones are already crossed off when filling the segments
+    let presieved: bool = presieve::covers(primes);
+    let first_prime: u32 = if presieved { presieve::PRESIEVE_PRIMES[presieve::PRESIEVE_PRIMES.len() - 1] + 1 } else { 7 };
+    let split: usize = primes.partition_point(|&prime| (prime as usize) <= segment_size);
+    let block_split: usize = primes.partition_point(|&prime| (prime as usize) <= block_size);
+    let first: usize = primes.partition_point(|&prime| prime < first_prime);
+    let small_primes: &[u32] = &primes[std::cmp::min(first, block_split)..block_split];
+    let medium_primes: &[u32] = &primes[block_split..split];
+    let large_primes: &[u32] = &primes[split..];
+
+    // A multiple moves at most 6 * (p / 30) + 6 bytes to the next one, so enough
+    // buckets are needed for the largest prime not to go around the list
+    let max_step: usize = large_primes.last().map_or(0, |&prime| 6 * (prime as usize / wheel::WHEEL) + 6);
+    let count: usize = (max_step >> shift) + 2;
+    self.buckets.iter_mut().for_each(Vec::clear);
+    self.buckets.resize_with(count, Vec::new);
+    self.small.clear();
+    self.medium.clear();
+
+    if low <= high {
+      let multiple = |&prime: &u32| Multiple::first(prime as usize, low.div_ceil(prime as usize), high);
+      self.small.extend(small_primes.iter().filter_map(multiple));
+      self.medium.extend(medium_primes.iter().filter_map(multiple));
+
+      for &prime in large_primes {
+        let prime: usize = prime as usize;
+        if let Some(multiple) = Multiple::first(prime, low.div_ceil(prime), high) {
+          self.buckets[((multiple.byte - first_byte) >> shift) % count].push(multiple);
+        }
+      }
+    }
+
+    self.sieve.resize(wheel::words::<W>(std::cmp::min(1 << shift, last_byte.saturating_sub(first_byte) + 1)), W::ONES);
+    self.presieved = presieved;
+    self.low = low;
+    self.high = high;
+    self.first_byte = first_byte;
+    self.last_byte = last_byte;
+    self.segment = 0;
+  }
+
+  // Sieve the next segment. Returns the multiple of 30 where it starts and
+  // its words, or None if there are no more segments
+  pub fn next_segment(&mut self) -> Option<(usize, &[W])> {
+
+    if self.low > self.high { return None }
+
+    let byte: usize = self.first_byte + (self.segment << self.shift);
+    if byte > self.last_byte { return None }
+
+    // Fill the segment, crossing off the multiples of the presieve primes if possible
+    let base: usize = byte * wheel::WHEEL;
+    let size: usize = std::cmp::min(1 << self.shift, self.last_byte - byte + 1);
+    let sieve: &mut [W] = &mut self.sieve[..wheel::words::<W>(size)];
+    if self.presieved { presieve::fill(sieve, base) } else { sieve.fill(W::ONES) }
+
+    // Set the bits that don't have to be sieved to not prime
+    if self.segment == 0 { wheel::unset_below(sieve, self.low - base) }
+    if byte + size > self.last_byte { wheel::unset_above(sieve, size - 1, self.high % wheel::WHEEL) }
+
+    // Cross off the multiples of the small primes one block at a time, and then the ones
+    // of the medium primes in the whole segment. Their next multiples stay for the next one
+    let block: usize = 1 << self.block_shift;
+    for offset in (0..size).step_by(block) {
+      let words: &mut [W] = &mut sieve[offset / wheel::bytes_per_word::<W>()..];
+      for multiple in self.small.iter_mut() {
+        multiple.cross_off(words, byte + offset, std::cmp::min(block, size - offset));
+      }
+    }
+    for multiple in self.medium.iter_mut() {
+      multiple.cross_off(sieve, byte, size);
+    }
+
+    // Cross off the multiples in the bucket of this segment, and move each
+    // prime to the bucket of its next multiple (if it's not past high)
+    let count: usize = self.buckets.len();
+    let mut bucket: Vec<Multiple> = std::mem::take(&mut self.buckets[self.segment % count]);
+    for mut multiple in bucket.drain(..) {
+      wheel::unset(sieve, multiple.byte - byte, multiple.bit());
+      multiple.advance();
+      if multiple.byte <= self.last_byte {
+        self.buckets[((multiple.byte - self.first_byte) >> self.shift) % count].push(multiple);
+      }
+    }
+    // Keep the allocation for the next time this bucket is used
+    self.buckets[self.segment % count] = bucket;
+
+    self.segment += 1;
+    Some((base, &self.sieve[..wheel::words::<W>(size)]))
+  }
+}
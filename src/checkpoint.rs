@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+// First line of every checkpoint file
+const HEADER: &str = "primeutils count checkpoint 1";
+
+// Set of chunk indices with the primes found in them, stored as sorted ranges
+// (both ends included) that don't touch, each one with the primes of its chunks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Chunks {
+  ranges: Vec<(usize, usize, usize)>,
+}
+
+impl Chunks {
+
+  pub fn new() -> Chunks {
+    Chunks { ranges: Vec::new() }
+  }
+
+  // Check if a chunk is in the set
+  pub fn contains(&self, chunk: usize) -> bool {
+    let i: usize = self.ranges.partition_point(|&(_, last, _)| last < chunk);
+    i < self.ranges.len() && self.ranges[i].0 <= chunk
+  }
+
+  // Add a chunk and its primes to the set, joining it with the ranges next to it.
+  // Returns false (without changing anything) if it was already there
+  pub fn insert(&mut self, chunk: usize, primes: usize) -> bool {
+
+    if self.contains(chunk) { return false }
+    let i: usize = self.ranges.partition_point(|&(_, last, _)| last < chunk);
+
+    let joins_previous: bool = i > 0 && self.ranges[i - 1].1 + 1 == chunk;
+    let joins_next: bool = i < self.ranges.len() && self.ranges[i].0 == chunk + 1;
+    match (joins_previous, joins_next) {
+      (true, true) => {
+        let (_, last, next_primes) = self.ranges.remove(i);
+        self.ranges[i - 1].1 = last;
+        self.ranges[i - 1].2 += primes + next_primes;
+      },
+      (true, false) => {
+        self.ranges[i - 1].1 = chunk;
+        self.ranges[i - 1].2 += primes;
+      },
+      (false, true) => {
+        self.ranges[i].0 = chunk;
+        self.ranges[i].2 += primes;
+      },
+      (false, false) => self.ranges.insert(i, (chunk, chunk, primes)),
+    }
+
+    true
+  }
+
+  // Count the chunks in the set
+  pub fn len(&self) -> usize {
+    self.ranges.iter().map(|&(first, last, _)| last - first + 1).sum()
+  }
+
+  // Count the primes of every chunk in the set
+  pub fn primes(&self) -> usize {
+    self.ranges.iter().map(|&(_, _, primes)| primes).sum()
+  }
+
+  // Get how many chunks from the first one are in the set, with their primes
+  pub fn prefix(&self) -> (usize, usize) {
+    match self.ranges.first() {
+      Some(&(0, last, primes)) => (last + 1, primes),
+      _ => (0, 0),
+    }
+  }
+}
+
+// Progress of a count split in chunks of chunk_size numbers from start, which can be saved to
+// a file to resume it later. The chunks can be done in any order, so the ones done are kept
+// as a set, with the primes found in them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+  pub start: usize,
+  pub limit: usize,
+  pub chunk_size: usize,
+  pub done: Chunks,
+}
+
+impl Checkpoint {
+
+  pub fn new(start: usize, limit: usize, chunk_size: usize) -> Checkpoint {
+    Checkpoint {
+      start,
+      limit,
+      chunk_size,
+      done: Chunks::new(),
+    }
+  }
+
+  // Number of chunks between start and limit
+  pub fn chunks(&self) -> usize {
+    (self.limit - self.start) / self.chunk_size + 1
+  }
+
+  // Get the first and the last numbers of a chunk
+  pub fn chunk_range(&self, chunk: usize) -> (usize, usize) {
+    let low: usize = self.start + chunk * self.chunk_size;
+    (low, std::cmp::min(low.saturating_add(self.chunk_size - 1), self.limit))
+  }
+
+  // Add the primes found in a chunk, if it wasn't done before
+  pub fn add(&mut self, chunk: usize, primes: usize) {
+    self.done.insert(chunk, primes);
+  }
+
+  // Get the last number of the chunks done from start without gaps (lower than start if
+  // there are none), and how many primes there are in them
+  pub fn covered(&self) -> (usize, usize) {
+    let (chunks, primes): (usize, usize) = self.done.prefix();
+    if chunks == self.chunks() { return (self.limit, primes) }
+    (self.start + chunks * self.chunk_size - 1, primes)
+  }
+
+  // Write the checkpoint to a file, as text with a value per line (the chunks done as
+  // FIRST-LAST:PRIMES). It is written to another file first and then renamed, so an
+  // interrupted write keeps the previous one
+  pub fn save(&self, path: &Path) -> Result<()> {
+
+    let done: Vec<String> = self.done.ranges.iter()
+      .map(|&(first, last, primes)| format!("{first}-{last}:{primes}"))
+      .collect();
+    let text: String = format!(
+      "{HEADER}\nstart {}\nlimit {}\nchunk {}\ndone {}\n",
+      self.start, self.limit, self.chunk_size, done.join(" ")
+    );
+
+    let mut temporary: std::ffi::OsString = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, text)?;
+    Ok(fs::rename(&temporary, path)?)
+  }
+
+  // Read a checkpoint written with save
+  pub fn load(path: &Path) -> Result<Checkpoint> {
+
+    let invalid = || Error::InvalidData(String::from("invalid count checkpoint"));
+    let text: String = fs::read_to_string(path)?;
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) { return Err(invalid()) }
+
+    // Read the value of a line, checking its name
+    let mut value = |name: &str| -> Result<String> {
+      let line: &str = lines.next().ok_or_else(invalid)?;
+      match line.split_once(' ') {
+        Some((key, value)) if key == name => Ok(String::from(value)),
+        _ if line == name => Ok(String::new()),
+        _ => Err(invalid()),
+      }
+    };
+    let number = |value: &str| value.parse::<usize>().map_err(|_| invalid());
+
+    let mut checkpoint: Checkpoint = Checkpoint::new(number(&value("start")?)?, number(&value("limit")?)?, number(&value("chunk")?)?);
+    if checkpoint.start > checkpoint.limit || checkpoint.chunk_size == 0 { return Err(invalid()) }
+
+    // The ranges must be sorted, not touching each other and inside the count
+    for range in value("done")?.split_whitespace() {
+      let (chunks, primes): (&str, &str) = range.split_once(':').ok_or_else(invalid)?;
+      let (first, last): (&str, &str) = chunks.split_once('-').ok_or_else(invalid)?;
+      let (first, last, primes): (usize, usize, usize) = (number(first)?, number(last)?, number(primes)?);
+      if first > last || last >= checkpoint.chunks() { return Err(invalid()) }
+      if checkpoint.done.ranges.last().is_some_and(|&(_, previous, _)| previous + 1 >= first) { return Err(invalid()) }
+      checkpoint.done.ranges.push((first, last, primes));
+    }
+
+    Ok(checkpoint)
+  }
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+
+use crate::error::Result;
+use crate::progress::{CancelToken, CountOptions, CountResult, Progress, ProgressCallback};
+
+// Limits from which Algorithm::Auto uses the Lagarias-Miller-Odlyzko algorithm
+pub const LMO_THRESHOLD: usize = 1 << 30;
+
+// How a count is done
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+  // LMO for limits from LMO_THRESHOLD when the count is from 0 and none of the settings only
+  // the sieve has (threads, segment bytes, a progress hook or a cancel token) is set, else the sieve
+  #[default]
+  Auto,
+  // The segmented sieve of count_primes
+  Sieve,
+  // The Lagarias-Miller-Odlyzko algorithm of count_primes_lmo, which only counts from 0
+  Lmo,
+}
+
+// Setting of a SieveConfig that can't be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+  // The threads are 0, so nothing would be sieved
+  ZeroThreads,
+  // The segments have 0 bytes
+  ZeroSegmentBytes,
+  // Algorithm::Lmo was chosen with a setting it doesn't have (a start, threads, segment
+  // bytes, a progress hook or a cancel token), named here
+  UnsupportedByLmo(&'static str),
+}
+
+impl fmt::Display for ConfigError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ConfigError::ZeroThreads => write!(f, "the number of threads must be at least 1"),
+      ConfigError::ZeroSegmentBytes => write!(f, "the segment size must be at least 1 byte"),
+      ConfigError::UnsupportedByLmo(setting) => write!(f, "the LMO algorithm can't count with {setting}"),
+    }
+  }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Check the settings of the sieve shared by SieveConfig and CountOptions
+pub(crate) fn check_sieve(threads: Option<usize>, segment_bytes: Option<usize>) -> Result<(), ConfigError> {
+  if threads == Some(0) { return Err(ConfigError::ZeroThreads) }
+  if segment_bytes == Some(0) { return Err(ConfigError::ZeroSegmentBytes) }
+  Ok(())
+}
+
+// Settings of a count of the primes between start and limit, built one at a time and checked
+// when counting. The threads and segment bytes not set are chosen like in count_primes_with
+#[derive(Default)]
+pub struct SieveConfig {
+  start: Option<usize>,
+  limit: usize,
+  threads: Option<usize>,
+  segment_bytes: Option<usize>,
+  algorithm: Algorithm,
+  progress: Option<ProgressCallback>,
+  cancel: Option<CancelToken>,
+}
+
+impl SieveConfig {
+
+  // Count the primes below or equal to limit
+  pub fn new(limit: usize) -> SieveConfig {
+    SieveConfig { limit, ..Default::default() }
+  }
+
+  // Count the primes from start instead of from 0, up to the limit of new (both included)
+  pub fn start(mut self, start: usize) -> SieveConfig {
+    self.start = Some(start);
+    self
+  }
+
+  pub fn threads(mut self, threads: usize) -> SieveConfig {
+    self.threads = Some(threads);
+    self
+  }
+
+  // Bytes of the blocks the small primes are crossed off in, which should fit in L1
+  // (the cache of CountOptions). The segments use L2 if it is larger
+  pub fn segment_bytes(mut self, bytes: usize) -> SieveConfig {
+    self.segment_bytes = Some(bytes);
+    self
+  }
+
+  pub fn algorithm(mut self, algorithm: Algorithm) -> SieveConfig {
+    self.algorithm = algorithm;
+    self
+  }
+
+  // Call progress from the thread that counts every time a chunk is sieved
+  pub fn progress<F: FnMut(&Progress) + 'static>(mut self, progress: F) -> SieveConfig {
+    self.progress = Some(Box::new(progress));
+    self
+  }
+
+  pub fn cancel(mut self, cancel: CancelToken) -> SieveConfig {
+    self.cancel = Some(cancel);
+    self
+  }
+
+  // Get the name of the first setting LMO can't use, if any
+  fn lmo_unsupported(&self) -> Option<&'static str> {
+    [
+      (self.start.is_some(), "a start"),
+      (self.threads.is_some(), "threads"),
+      (self.segment_bytes.is_some(), "segment bytes"),
+      (self.progress.is_some(), "a progress hook"),
+      (self.cancel.is_some(), "a cancel token"),
+    ].iter().find(|(set, _)| *set).map(|&(_, setting)| setting)
+  }
+
+  // Check that the settings can be used to count
+  pub fn validate(&self) -> Result<(), ConfigError> {
+    check_sieve(self.threads, self.segment_bytes)?;
+    if self.algorithm == Algorithm::Lmo {
+      if let Some(setting) = self.lmo_unsupported() { return Err(ConfigError::UnsupportedByLmo(setting)) }
+    }
+    Ok(())
+  }
+
+  // Get the algorithm the count uses, Sieve or Lmo, choosing the one of Auto
+  // for the limit and the settings set until now
+  pub fn chosen_algorithm(&self) -> Algorithm {
+    match self.algorithm {
+      Algorithm::Auto if self.limit >= LMO_THRESHOLD && self.lmo_unsupported().is_none() => Algorithm::Lmo,
+      Algorithm::Auto => Algorithm::Sieve,
+      algorithm => algorithm,
+    }
+  }
+
+  // Count the primes with these settings, or get the first one that can't be used
+  // (as Error::Config) or why the count failed
+  pub fn count(self) -> Result<CountResult> {
+
+    self.validate()?;
+    if self.chosen_algorithm() == Algorithm::Lmo {
+      return Ok(CountResult { primes: crate::count_primes_lmo(self.limit), start: 2, end: self.limit, cancelled: false });
+    }
+
+    crate::count_primes_with(self.limit, self.start, CountOptions {
+      threads: self.threads,
+      cache: self.segment_bytes,
+      progress: self.progress,
+      cancel: self.cancel,
+    })
+  }
+}
@@ -0,0 +1,298 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::checkpoint::Checkpoint;
+use crate::error::{Error, Result};
+use crate::progress::{CountOptions, CountResult, Progress, ProgressCallback};
+
+// Line-based protocol between the coordinator and its workers, over TCP:
+//   coordinator: PRIMEUTILS 1                 when a worker connects
+//   worker:      NEXT                         to ask for work
+//   coordinator: WORK <id> <low> <high>       count the primes between low and high (both included)
+//                WAIT <ms>                    every range is taken, ask again later
+//                DONE                         the count is finished
+//   worker:      RESULT <id> <primes>         after counting a range
+//   coordinator: OK
+//                ERROR <message>              after an invalid line or the result of a range not given
+//                                             to this worker, and then it closes the connection
+const GREETING: &str = "PRIMEUTILS 1";
+
+// Time a worker has to return the result of a range before it is given to another one
+const WORK_TIMEOUT: Duration = Duration::from_secs(600);
+// Time the workers wait before asking again when every range is taken
+const WAIT_TIME: Duration = Duration::from_millis(500);
+// Chunks of count_primes in each range, so the threads of a worker share its work
+const WORK_CHUNKS: usize = 64;
+// Time between the checks of the coordinator for new workers
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+// Time the workers have to ask for work again (and be told the count is done) after
+// it is finished, before their connections are closed
+const CLOSE_TIME: Duration = Duration::from_secs(1);
+
+// Options of serve_coordinator. The work size is chosen for the limit if not set
+#[derive(Default)]
+pub struct CoordinatorOptions {
+  // Numbers in each range given to a worker
+  pub work_size: Option<usize>,
+  // Time a worker has to return a range, WORK_TIMEOUT by default
+  pub timeout: Option<Duration>,
+  // Called from the thread that started the coordinator every time a range is counted
+  pub progress: Option<ProgressCallback>,
+}
+
+// Ranges of the count, the ones done with their primes and the ones given to a worker
+struct State {
+  checkpoint: Checkpoint,
+  // Deadline of each range given and not done yet
+  assigned: HashMap<usize, Instant>,
+  // Ranges below this have been given at least once
+  next: usize,
+  finished: bool,
+}
+
+enum Reply {
+  Work(usize, usize, usize),
+  Wait,
+  Done,
+}
+
+impl State {
+
+  // Choose the work for a worker: a range given to another worker that timed out,
+  // else the next range not given yet, else wait for the ones given
+  fn next_work(&mut self, timeout: Duration) -> Reply {
+
+    let now: Instant = Instant::now();
+    let expired: Option<usize> = self.assigned.iter()
+      .filter(|&(_, &deadline)| deadline <= now)
+      .map(|(&id, _)| id)
+      .min();
+
+    let id: usize = match expired {
+      Some(id) => id,
+      None if self.next < self.checkpoint.chunks() => {
+        self.next += 1;
+        self.next - 1
+      },
+      None if self.assigned.is_empty() => return Reply::Done,
+      None => return Reply::Wait,
+    };
+
+    self.assigned.insert(id, now + timeout);
+    let (low, high): (usize, usize) = self.checkpoint.chunk_range(id);
+    Reply::Work(id, low, high)
+  }
+
+  // Add the result of a range, unless it was already done by another worker
+  fn add_result(&mut self, id: usize, primes: usize) {
+    self.assigned.remove(&id);
+    self.checkpoint.add(id, primes);
+    if self.next == self.checkpoint.chunks() && self.assigned.is_empty() { self.finished = true }
+  }
+}
+
+// Answer the lines of a worker until it disconnects
+fn serve_worker(stream: &TcpStream, state: Arc<(Mutex<State>, Condvar)>, timeout: Duration) -> io::Result<()> {
+
+  // A worker that stops sending lines has lost its ranges already, so stop waiting for it after a while
+  stream.set_nonblocking(false)?;
+  stream.set_read_timeout(Some(timeout * 2))?;
+  let mut reader: BufReader<&TcpStream> = BufReader::new(stream);
+  let mut writer: &TcpStream = stream;
+  writeln!(writer, "{GREETING}")?;
+
+  // Ranges given to this worker and not returned yet
+  let mut given: HashSet<usize> = HashSet::new();
+
+  let mut line: String = String::new();
+  loop {
+    line.clear();
+    if reader.read_line(&mut line)? == 0 { return Ok(()) }
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    let reply: String = match words.as_slice() {
+      ["NEXT"] => match state.0.lock().unwrap().next_work(timeout) {
+        Reply::Work(id, low, high) => {
+          given.insert(id);
+          format!("WORK {id} {low} {high}")
+        },
+        Reply::Wait => format!("WAIT {}", WAIT_TIME.as_millis()),
+        Reply::Done => String::from("DONE"),
+      },
+
+      ["RESULT", id, primes] => {
+        let (lock, condvar) = &*state;
+        let mut state = lock.lock().unwrap();
+        match (id.parse::<usize>(), primes.parse::<usize>()) {
+          // Only the ranges given to this worker are taken, even if they were given to another one since
+          (Ok(id), Ok(primes)) if given.remove(&id) => {
+            state.add_result(id, primes);
+            condvar.notify_all();
+            String::from("OK")
+          },
+          (Ok(id), Ok(_)) if id < state.checkpoint.chunks() => {
+            writeln!(writer, "ERROR range {id} was not given to this worker")?;
+            return Ok(());
+          },
+          _ => {
+            writeln!(writer, "ERROR invalid result")?;
+            return Ok(());
+          },
+        }
+      },
+
+      _ => {
+        writeln!(writer, "ERROR unknown command")?;
+        return Ok(());
+      },
+    };
+
+    writeln!(writer, "{reply}")?;
+  }
+}
+
+// Wait a while for the threads serving the workers to finish (which they do when the workers
+// disconnect), and then close the connections of the ones left and wait for them too
+fn close_workers(workers: Vec<(TcpStream, thread::JoinHandle<io::Result<()>>)>) {
+
+  let deadline: Instant = Instant::now() + CLOSE_TIME;
+  while Instant::now() < deadline && workers.iter().any(|(_, handle)| !handle.is_finished()) {
+    thread::sleep(ACCEPT_INTERVAL);
+  }
+
+  for (stream, handle) in workers {
+    if !handle.is_finished() { let _ = stream.shutdown(Shutdown::Both); }
+    // A worker that fails only loses its connection, and its range is given to another one
+    let _ = handle.join();
+  }
+}
+
+// Coordinate a count of the primes between start and limit among the workers connecting to
+// listener, giving each one a range at a time. The ranges of workers that don't return them
+// in time are given to other workers. Returns when every range has been counted and the
+// connections of the workers are closed, or an error if no more workers can connect
+pub fn serve_coordinator(listener: TcpListener, limit: usize, start: Option<usize>, options: CoordinatorOptions) -> Result<CountResult> {
+
+  let start: usize = std::cmp::max(start.unwrap_or(2), 2);
+  if limit < 2 || start > limit { return Ok(CountResult { primes: 0, start, end: limit, cancelled: false }) }
+
+  let mut options: CoordinatorOptions = options;
+  let timeout: Duration = options.timeout.unwrap_or(WORK_TIMEOUT);
+  let work_size: usize = options.work_size
+    .unwrap_or(crate::chunk_size(start, limit, 1, crate::default_cache()).saturating_mul(WORK_CHUNKS))
+    .max(1);
+
+  let state: Arc<(Mutex<State>, Condvar)> = Arc::new((Mutex::new(State {
+    checkpoint: Checkpoint::new(start, limit, work_size),
+    assigned: HashMap::new(),
+    next: 0,
+    finished: false,
+  }), Condvar::new()));
+
+  // Accept the workers in another thread, checking from time to time if the count is finished
+  listener.set_nonblocking(true)?;
+  let accept_state: Arc<(Mutex<State>, Condvar)> = Arc::clone(&state);
+  let acceptor = thread::spawn(move || -> io::Result<()> {
+    let mut workers: Vec<(TcpStream, thread::JoinHandle<io::Result<()>>)> = Vec::new();
+    let mut result: io::Result<()> = Ok(());
+    while !accept_state.0.lock().unwrap().finished {
+      // The threads of the workers that disconnected are done, so drop their connections
+      workers.retain(|(_, handle)| !handle.is_finished());
+
+      match listener.accept() {
+        Ok((stream, _)) => {
+          let state: Arc<(Mutex<State>, Condvar)> = Arc::clone(&accept_state);
+          let closer: TcpStream = match stream.try_clone() {
+            Ok(closer) => closer,
+            Err(_) => continue,
+          };
+          workers.push((closer, thread::spawn(move || {
+            let result: io::Result<()> = serve_worker(&stream, state, timeout);
+            // The acceptor keeps a handle to the connection, so close it here
+            let _ = stream.shutdown(Shutdown::Both);
+            result
+          })));
+        },
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+        Err(err) => {
+          result = Err(err);
+          break
+        },
+      }
+    }
+    close_workers(workers);
+    result
+  });
+
+  // Wait for the ranges, reporting the progress
+  let now: Instant = Instant::now();
+  let (lock, condvar) = &*state;
+  let mut guard = lock.lock().unwrap();
+  let mut reported: usize = 0;
+  loop {
+    let done: usize = guard.checkpoint.done.len();
+    if done != reported {
+      reported = done;
+      if let Some(progress) = options.progress.as_mut() {
+        progress(&Progress { done, total: guard.checkpoint.chunks(), primes: guard.checkpoint.done.primes(), elapsed: now.elapsed() });
+      }
+    }
+    if guard.finished { break }
+    // Stop if the listener fails, as no more workers could connect
+    if acceptor.is_finished() { break }
+    guard = condvar.wait_timeout(guard, ACCEPT_INTERVAL).unwrap().0;
+  }
+  let (end, primes): (usize, usize) = guard.checkpoint.covered();
+  drop(guard);
+
+  acceptor.join().map_err(|_| Error::ThreadPanicked)??;
+  // The acceptor only stops without an error when the count is finished
+  Ok(CountResult { primes, start, end, cancelled: end < limit })
+}
+
+// Connect to a coordinator and count the ranges it gives until the count is finished,
+// with the threads and the cache of count_primes. Returns how many ranges were counted
+pub fn run_worker<A: ToSocketAddrs>(address: A, threads: Option<usize>, cache: Option<usize>) -> Result<usize> {
+
+  let invalid = |line: &str| Error::InvalidData(format!("unexpected line from the coordinator: {:?}", line.trim_end()));
+
+  let stream: TcpStream = TcpStream::connect(address)?;
+  let mut reader: BufReader<TcpStream> = BufReader::new(stream.try_clone()?);
+  let mut writer: TcpStream = stream;
+
+  let mut line: String = String::new();
+  reader.read_line(&mut line)?;
+  if line.trim_end() != GREETING { return Err(invalid(&line)) }
+
+  let mut ranges: usize = 0;
+  loop {
+    writeln!(writer, "NEXT")?;
+    line.clear();
+    // The connection is closed if the coordinator exits after the count is finished
+    if reader.read_line(&mut line)? == 0 { return Ok(ranges) }
+
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+      ["WORK", id, low, high] => {
+        let parse = |word: &str| word.parse::<usize>().map_err(|_| invalid(&line));
+        let (id, low, high): (usize, usize, usize) = (parse(id)?, parse(low)?, parse(high)?);
+
+        let primes: usize = crate::count_primes_with(high, Some(low), CountOptions { threads, cache, ..Default::default() })?.primes;
+        writeln!(writer, "RESULT {id} {primes}")?;
+        ranges += 1;
+
+        line.clear();
+        reader.read_line(&mut line)?;
+        if line.trim_end() != "OK" { return Err(invalid(&line)) }
+      },
+      ["WAIT", time] => thread::sleep(Duration::from_millis(time.parse::<u64>().map_err(|_| invalid(&line))?)),
+      ["DONE"] => return Ok(ranges),
+      _ => return Err(invalid(&line)),
+    }
+  }
+}
@@ -1,33 +1,271 @@
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// Caches of the first CPU, as described by Linux
+const SYSFS_CACHE: &str = "/sys/devices/system/cpu/cpu0/cache";
+// L1 data cache size used when it can't be found
+const DEFAULT_L1: usize = 32768;
+// Environment variable that sets the threads, over every other source
+pub const THREADS_VARIABLE: &str = "PRIMEUTILS_THREADS";
+// Where the cgroups of the process are described, and where cgroups are mounted
+const PROC_CGROUP: &str = "/proc/self/cgroup";
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+// A data (or unified) cache: its size in bytes and how many logical CPUs share it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cache {
+  pub size: usize,
+  pub shared_by: usize,
+}
+
+impl Cache {
+
+  // Get the part of the cache for each CPU sharing it
+  pub fn per_cpu(&self) -> usize {
+    self.size / std::cmp::max(self.shared_by, 1)
+  }
+}
+
+// Data caches of the processor, each one None if it wasn't found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheInfo {
+  pub l1: Option<Cache>,
+  pub l2: Option<Cache>,
+  pub l3: Option<Cache>,
+}
+
+impl CacheInfo {
+
+  // Get the bytes of L1 for each CPU, or DEFAULT_L1 if it wasn't found
+  pub fn l1_size(&self) -> usize {
+    self.l1.map_or(DEFAULT_L1, |cache| cache.per_cpu()).max(1)
+  }
+
+  // Get the bytes of L2 for each CPU (never less than L1), or the L1 size if it wasn't found
+  pub fn l2_size(&self) -> usize {
+    self.l2.map_or(0, |cache| cache.per_cpu()).max(self.l1_size())
+  }
+
+  fn set(&mut self, level: usize, cache: Cache) {
+    let slot: &mut Option<Cache> = match level {
+      1 => &mut self.l1,
+      2 => &mut self.l2,
+      3 => &mut self.l3,
+      _ => return,
+    };
+    // Keep the first cache of each level
+    if slot.is_none() { *slot = Some(cache) }
+  }
+}
+
+// Parse a cache size of sysfs, like "48K", "2048K" or "30M"
+pub fn parse_size(text: &str) -> Option<usize> {
+  let text: &str = text.trim();
+  let (number, unit): (&str, usize) = match text.as_bytes().last()? {
+    b'K' | b'k' => (&text[..text.len() - 1], 1 << 10),
+    b'M' | b'm' => (&text[..text.len() - 1], 1 << 20),
+    b'G' | b'g' => (&text[..text.len() - 1], 1 << 30),
+    _ => (text, 1),
+  };
+  number.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+// Count the CPUs of a sysfs CPU list, like "0-3,8-11"
+pub fn parse_cpu_list(text: &str) -> Option<usize> {
+  let mut cpus: usize = 0;
+  for range in text.trim().split(',') {
+    let (first, last): (&str, &str) = range.split_once('-').unwrap_or((range, range));
+    let (first, last): (usize, usize) = (first.parse().ok()?, last.parse().ok()?);
+    if first > last { return None }
+    cpus += last - first + 1;
+  }
+  Some(cpus)
+}
+
+// Read the data caches from a sysfs cache directory (with an index* directory for each
+// cache), or None if it can't be read or has none
+pub fn read_sysfs_cache(dir: &Path) -> Option<CacheInfo> {
+
+  // Read the indices in order, so the first cache of each level is the same every time
+  let mut entries: Vec<std::path::PathBuf> = fs::read_dir(dir).ok()?
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.path())
+    .filter(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("index")))
+    .collect();
+  entries.sort();
+
+  let mut info: CacheInfo = CacheInfo::default();
+  for entry in entries {
+    let read = |name: &str| fs::read_to_string(entry.join(name)).ok();
+
+    // The instruction caches don't hold the sieve
+    let Some(kind) = read("type") else { continue };
+    if kind.trim() != "Data" && kind.trim() != "Unified" { continue }
+    let Some(level) = read("level").and_then(|level| level.trim().parse::<usize>().ok()) else { continue };
+    let Some(size) = read("size").and_then(|size| parse_size(&size)) else { continue };
+    let shared_by: usize = read("shared_cpu_list").and_then(|list| parse_cpu_list(&list)).unwrap_or(1);
+
+    info.set(level, Cache { size, shared_by });
+  }
+
+  if info == CacheInfo::default() { return None }
+  Some(info)
+}
+
+// Read the data caches with CPUID, or None if it doesn't describe them
+#[cfg(feature = "get_cpu_info")]
+fn read_cpuid_cache() -> Option<CacheInfo> {
+
+  let cpuid: raw_cpuid::CpuId<raw_cpuid::CpuIdReaderNative> = raw_cpuid::CpuId::new();
+  let mut info: CacheInfo = CacheInfo::default();
+
+  for cache in cpuid.get_cache_parameters()? {
+    if cache.cache_type() != raw_cpuid::CacheType::Data && cache.cache_type() != raw_cpuid::CacheType::Unified { continue }
+    let size: usize = cache.associativity() * cache.physical_line_partitions() * cache.coherency_line_size() * cache.sets();
+    info.set(cache.level() as usize, Cache { size, shared_by: cache.max_cores_for_cache() });
+  }
+
+  if info == CacheInfo::default() { return None }
+  Some(info)
+}
+
+// Get the data caches of the processor from sysfs, else from CPUID. It is only looked up once
+pub fn get_cache_info() -> CacheInfo {
+
+  static INFO: OnceLock<CacheInfo> = OnceLock::new();
+  *INFO.get_or_init(|| {
+    if let Some(info) = read_sysfs_cache(Path::new(SYSFS_CACHE)) { return info }
+
+    #[cfg(feature = "get_cpu_info")]
+    if let Some(info) = read_cpuid_cache() { return info }
+
+    CacheInfo::default()
+  })
+}
+
 // Get the L1 cache size of the processor in bytes
 pub fn get_cache_size() -> usize {
+  get_cache_info().l1_size()
+}
 
-  #[cfg(feature = "get_cpu_info")]
-  {
-    let cpuid: raw_cpuid::CpuId<raw_cpuid::CpuIdReaderNative> = raw_cpuid::CpuId::new();
-  
-    if let Some(cparams) = cpuid.get_cache_parameters() {
-      for cache in cparams {
-        if cache.cache_type() == raw_cpuid::CacheType::Data {
-          if cache.level() == 1 {
-            return cache.associativity() * cache.physical_line_partitions() * cache.coherency_line_size() * cache.sets();
+// Where the number of threads to use comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadSource {
+  // The PRIMEUTILS_THREADS environment variable
+  Env,
+  // The CPU quota of a cgroup (cpu.max, or cpu.cfs_quota_us in cgroup v1)
+  Quota,
+  // The CPUs the process may run on, from available_parallelism (which uses sched_getaffinity)
+  Affinity,
+  // The CPUs of the machine, or 1 if they can't be found
+  Fallback,
+}
+
+impl std::fmt::Display for ThreadSource {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str(match self {
+      ThreadSource::Env => "env",
+      ThreadSource::Quota => "quota",
+      ThreadSource::Affinity => "affinity",
+      ThreadSource::Fallback => "fallback",
+    })
+  }
+}
+
+// Threads to use and where that number comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadCount {
+  pub threads: usize,
+  pub source: ThreadSource,
+}
+
+// Choose the threads from the environment variable if it is a positive number, else from the
+// lowest of the quota and the affinity (the quota if they are the same, as the affinity
+// of available_parallelism is lowered to the quota), else the fallback
+pub fn choose_threads(env: Option<&str>, quota: Option<usize>, affinity: Option<usize>, fallback: usize) -> ThreadCount {
+
+  if let Some(threads) = env.and_then(|env| env.trim().parse::<usize>().ok()).filter(|&threads| threads > 0) {
+    return ThreadCount { threads, source: ThreadSource::Env };
+  }
+
+  match (quota.filter(|&quota| quota > 0), affinity.filter(|&affinity| affinity > 0)) {
+    (Some(quota), Some(affinity)) if quota <= affinity => ThreadCount { threads: quota, source: ThreadSource::Quota },
+    (_, Some(affinity)) => ThreadCount { threads: affinity, source: ThreadSource::Affinity },
+    (Some(quota), None) => ThreadCount { threads: quota, source: ThreadSource::Quota },
+    (None, None) => ThreadCount { threads: std::cmp::max(fallback, 1), source: ThreadSource::Fallback },
+  }
+}
+
+// Get the CPUs (rounded up) of a cgroup v2 cpu.max, like "150000 100000", or None if it is "max"
+pub fn parse_cpu_max(text: &str) -> Option<usize> {
+  let (quota, period): (&str, &str) = text.trim().split_once(' ')?;
+  cpus_of_quota(quota, period)
+}
+
+// Get the CPUs (rounded up) of a quota and a period in microseconds, or None if the quota
+// is not a positive number (like "max" in cgroup v2 or -1 in cgroup v1)
+fn cpus_of_quota(quota: &str, period: &str) -> Option<usize> {
+  let quota: u64 = quota.trim().parse().ok().filter(|&quota| quota > 0)?;
+  let period: u64 = period.trim().parse().ok().filter(|&period| period > 0)?;
+  usize::try_from(quota.div_ceil(period)).ok()
+}
+
+// Read the lowest CPU quota of the cgroups of the process, described as in /proc/self/cgroup,
+// from the cgroup hierarchies mounted in root. The quota of a cgroup also limits the ones
+// inside it, so the parents are read too. None if there is no quota
+pub fn read_cgroup_quota(root: &Path, cgroups: &str) -> Option<usize> {
+
+  let mut lowest: Option<usize> = None;
+  let mut add = |cpus: Option<usize>| {
+    if let Some(cpus) = cpus { lowest = Some(lowest.map_or(cpus, |lowest| lowest.min(cpus))) }
+  };
+
+  // Each line is ID:CONTROLLERS:PATH, with no controllers for cgroup v2
+  for line in cgroups.lines() {
+    let mut fields = line.splitn(3, ':');
+    let (Some(_), Some(controllers), Some(path)) = (fields.next(), fields.next(), fields.next()) else { continue };
+    let path: &Path = Path::new(path.trim_start_matches('/'));
+
+    if controllers.is_empty() {
+      for dir in path.ancestors() {
+        add(fs::read_to_string(root.join(dir).join("cpu.max")).ok().and_then(|text| parse_cpu_max(&text)));
+      }
+    }
+    else if controllers.split(',').any(|controller| controller == "cpu") {
+      // The cpu controller may be mounted alone or with others, like cpu,cpuacct
+      for mount in [controllers, "cpu"] {
+        for dir in path.ancestors() {
+          let dir: std::path::PathBuf = root.join(mount).join(dir);
+          let read = |name: &str| fs::read_to_string(dir.join(name)).ok();
+          if let (Some(quota), Some(period)) = (read("cpu.cfs_quota_us"), read("cpu.cfs_period_us")) {
+            add(cpus_of_quota(&quota, &period));
           }
         }
       }
     }
   }
-  
-  32768
+
+  lowest
 }
 
-// Get the number of cores in the processor
-pub fn get_cores() -> usize {
+// Get the threads to use and where that number comes from: PRIMEUTILS_THREADS, else the
+// lowest of the cgroup CPU quota and the affinity mask, else the CPUs of the machine
+pub fn get_thread_count() -> ThreadCount {
+
+  let env: Option<String> = std::env::var(THREADS_VARIABLE).ok();
+  let quota: Option<usize> = fs::read_to_string(PROC_CGROUP).ok().and_then(|cgroups| read_cgroup_quota(Path::new(CGROUP_ROOT), &cgroups));
+  let affinity: Option<usize> = std::thread::available_parallelism().ok().map(|threads| threads.get());
+
   #[cfg(feature = "get_cpu_info")]
-  {
-    let cores: usize = num_cpus::get();
-    if cores > 0 {
-      return cores;
-    }
-  }
+  let fallback: usize = num_cpus::get();
+  #[cfg(not(feature = "get_cpu_info"))]
+  let fallback: usize = 1;
+
+  choose_threads(env.as_deref(), quota, affinity, fallback)
+}
 
-  1
+// Get the number of threads to use, see get_thread_count
+pub fn get_cores() -> usize {
+  get_thread_count().threads
 }
@@ -0,0 +1,75 @@
+use std::fmt;
+use std::io;
+
+use crate::config::ConfigError;
+
+// Error of the entry points of the library
+#[derive(Debug)]
+pub enum Error {
+  // A setting of the count can't be used
+  Config(ConfigError),
+  // A thread sieving the count panicked, so its chunks weren't counted
+  ThreadPanicked,
+  // The checkpoint to resume is of the count between these numbers, not of the one asked for
+  CheckpointMismatch { start: usize, limit: usize },
+  // The range between start and limit has fewer numbers than shards
+  TooManyShards { start: usize, limit: usize, shards: usize },
+  // The partial counts to merge aren't of every shard of the same count, which is described here
+  ShardMismatch(String),
+  // 0 was given where only positive numbers make sense: it is a multiple of
+  // every prime, so it has no prime factorization, and there is no 0th prime
+  Zero,
+  // The factors given to a Factorization aren't all prime, or their product doesn't fit in 64 bits
+  InvalidFactors,
+  // The result doesn't fit in the integer type returned
+  Overflow,
+  // There isn't enough memory for a sieve of the range
+  OutOfMemory,
+  // A file or a message read isn't valid, which is described here
+  InvalidData(String),
+  // Reading or writing a file or a connection failed
+  Io(io::Error),
+}
+
+// Result of the entry points of the library
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::Config(err) => write!(f, "invalid count settings: {err}"),
+      Error::ThreadPanicked => write!(f, "a thread of the count panicked"),
+      Error::CheckpointMismatch { start, limit } => write!(f, "the checkpoint is of the count between {start} and {limit}"),
+      Error::TooManyShards { start, limit, shards } => write!(f, "the range between {start} and {limit} can't be split in {shards} shards"),
+      Error::ShardMismatch(message) => write!(f, "{message}"),
+      Error::Zero => write!(f, "the number must be at least 1"),
+      Error::InvalidFactors => write!(f, "the factors aren't primes with a product of 64 bits"),
+      Error::Overflow => write!(f, "the result doesn't fit in its integer type"),
+      Error::OutOfMemory => write!(f, "there isn't enough memory to sieve the range"),
+      Error::InvalidData(message) => write!(f, "{message}"),
+      Error::Io(err) => err.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Error::Config(err) => Some(err),
+      Error::Io(err) => Some(err),
+      _ => None,
+    }
+  }
+}
+
+impl From<ConfigError> for Error {
+  fn from(err: ConfigError) -> Error {
+    Error::Config(err)
+  }
+}
+
+impl From<io::Error> for Error {
+  fn from(err: io::Error) -> Error {
+    Error::Io(err)
+  }
+}
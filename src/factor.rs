@@ -0,0 +1,231 @@
+use std::fmt;
+
+use crate::error::Error;
+use crate::gcd;
+use crate::is_prime;
+use crate::lcm;
+use crate::primality::mul_mod;
+
+// Apply the pseudorandom function x^2 + c modulo n
+#[inline]
+fn next(x: u64, c: u64, n: u64) -> u64 {
+  ((x as u128 * x as u128 + c as u128) % n as u128) as u64
+}
+
+// Find a non trivial divisor of the odd composite number n
+// using Brent's variant of Pollard's rho algorithm
+pub fn pollard_brent(n: u64) -> u64 {
+
+  // How many differences are multiplied together before computing the gcd
+  const BATCH: u64 = 128;
+
+  // Try with increasing constants until a proper divisor is found
+  let mut c: u64 = 1;
+  loop {
+    let mut x: u64 = 0;
+    let mut y: u64 = 2;
+    let mut ys: u64 = y;
+    let mut q: u64 = 1;
+    let mut g: u64 = 1;
+    let mut r: u64 = 1;
+
+    while g == 1 {
+      x = y;
+      for _ in 0..r {
+        y = next(y, c, n);
+      }
+
+      // Look for a cycle in batches, saving the starting point of each one
+      let mut k: u64 = 0;
+      while k < r && g == 1 {
+        ys = y;
+        for _ in 0..std::cmp::min(BATCH, r - k) {
+          y = next(y, c, n);
+          q = mul_mod(q, x.abs_diff(y), n);
+        }
+        g = gcd(q, n);
+        k += BATCH;
+      }
+
+      r *= 2;
+    }
+
+    // If the batch overshot, go back and check one step at a time
+    if g == n {
+      loop {
+        ys = next(ys, c, n);
+        g = gcd(x.abs_diff(ys), n);
+        if g > 1 { break }
+      }
+    }
+
+    if g != n { return g }
+    c += 1;
+  }
+}
+
+// Split n into its prime factors, pushing them (unsorted) to factors
+pub fn split_composite(n: u64, factors: &mut Vec<u64>) {
+
+  if n == 1 { return }
+
+  if is_prime(n) {
+    factors.push(n);
+    return;
+  }
+
+  let divisor: u64 = pollard_brent(n);
+  split_composite(divisor, factors);
+  split_composite(n / divisor, factors);
+}
+
+// The prime factors of a number, stored as (prime, exponent) pairs sorted by prime.
+// Their product always fits in 64 bits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Factorization {
+  factors: Vec<(u64, u32)>,
+}
+
+impl Factorization {
+
+  // Get the (prime, exponent) pairs
+  pub fn factors(&self) -> &[(u64, u32)] {
+    &self.factors
+  }
+
+  // Group a sorted flat vector of prime factors
+  pub(crate) fn group(flat: Vec<u64>) -> Factorization {
+    let mut factors: Vec<(u64, u32)> = Vec::new();
+    for factor in flat {
+      match factors.last_mut() {
+        Some((prime, exponent)) if *prime == factor => *exponent += 1,
+        _ => factors.push((factor, 1)),
+      }
+    }
+
+    Factorization { factors }
+  }
+
+  // Multiply the factors back into the original number
+  pub fn value(&self) -> u64 {
+    self.factors.iter().map(|&(prime, exponent)| prime.pow(exponent)).product()
+  }
+
+  // Get all the divisors of the number, sorted
+  pub fn divisors(&self) -> Vec<u64> {
+
+    let mut divisors: Vec<u64> = vec![1];
+
+    // Multiply every divisor found so far by each power of the next prime
+    for &(prime, exponent) in self.factors.iter() {
+      let current: usize = divisors.len();
+      let mut power: u64 = 1;
+
+      for _ in 0..exponent {
+        power *= prime;
+        for i in 0..current {
+          divisors.push(divisors[i] * power);
+        }
+      }
+    }
+
+    divisors.sort_unstable();
+    divisors
+  }
+
+  // Number of divisors, τ(n)
+  pub fn divisor_count(&self) -> u64 {
+    self.factors.iter().map(|&(_, exponent)| exponent as u64 + 1).product()
+  }
+
+  // Sum of the k-th powers of the divisors, σ_k(n),
+  // or None if it doesn't fit in 128 bits
+  pub fn divisor_sum(&self, k: u32) -> Option<u128> {
+    self.factors.iter().try_fold(1u128, |acc, &(prime, exponent)| {
+      // 1 + p^k + p^2k + ... + p^(exponent * k)
+      let step: u128 = (prime as u128).checked_pow(k)?;
+      let mut sum: u128 = 1;
+      let mut power: u128 = 1;
+      for _ in 0..exponent {
+        power = power.checked_mul(step)?;
+        sum = sum.checked_add(power)?;
+      }
+
+      acc.checked_mul(sum)
+    })
+  }
+
+  // Euler's totient function, φ(n)
+  pub fn totient(&self) -> u64 {
+    self.factors.iter().map(|&(prime, exponent)| (prime - 1) * prime.pow(exponent - 1)).product()
+  }
+
+  // Möbius function, μ(n)
+  pub fn mobius(&self) -> i8 {
+    if self.factors.iter().any(|&(_, exponent)| exponent > 1) { return 0 }
+    if self.factors.len().is_multiple_of(2) { 1 } else { -1 }
+  }
+
+  // Product of the distinct prime factors, rad(n)
+  pub fn radical(&self) -> u64 {
+    self.factors.iter().map(|&(prime, _)| prime).product()
+  }
+
+  // Carmichael's function, λ(n): the lowest m such that a^m = 1 mod n for every a coprime to n
+  pub fn carmichael(&self) -> u64 {
+    self.factors.iter().fold(1u64, |acc, &(prime, exponent)| {
+      let lambda: u64 = if prime == 2 && exponent >= 3 {
+        // The group of units modulo 2^k isn't cyclic for k >= 3
+        1 << (exponent - 2)
+      }
+      else {
+        (prime - 1) * prime.pow(exponent - 1)
+      };
+
+      // The result divides φ(n), so it always fits in 64 bits
+      lcm(acc, lambda) as u64
+    })
+  }
+}
+
+impl TryFrom<Vec<u64>> for Factorization {
+  type Error = Error;
+
+  // Group a flat vector of factors, such as the one returned by split_into_factors. Returns
+  // Error::InvalidFactors if one of them isn't prime or their product doesn't fit in 64 bits
+  fn try_from(mut flat: Vec<u64>) -> Result<Self, Error> {
+    flat.sort_unstable();
+
+    if !flat.iter().all(|&factor| is_prime(factor)) { return Err(Error::InvalidFactors) }
+    if flat.iter().try_fold(1u64, |acc, &factor| acc.checked_mul(factor)).is_none() { return Err(Error::InvalidFactors) }
+
+    Ok(Factorization::group(flat))
+  }
+}
+
+impl From<Factorization> for Vec<u64> {
+
+  // Expand the factorization into a sorted flat vector of factors
+  fn from(factorization: Factorization) -> Self {
+    factorization.factors.iter()
+      .flat_map(|&(prime, exponent)| std::iter::repeat_n(prime, exponent as usize))
+      .collect()
+  }
+}
+
+impl fmt::Display for Factorization {
+
+  // Render the factors as "2^2 * 3^2 * 7 * 11". The empty product is 1
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.factors.is_empty() { return write!(f, "1") }
+
+    for (i, &(prime, exponent)) in self.factors.iter().enumerate() {
+      if i > 0 { write!(f, " * ")? }
+
+      if exponent == 1 { write!(f, "{}", prime)? }
+      else { write!(f, "{}^{}", prime, exponent)? }
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,99 @@
+use crate::cpu;
+use crate::is_prime;
+use crate::segment_primes;
+use crate::sieve_limit;
+use crate::try_simple_sieve;
+use crate::try_vec;
+use crate::wheel;
+use crate::Result;
+
+// Iterator over the prime numbers between start and end (both included),
+// sieving one segment at a time so memory stays bounded
+pub struct PrimeIter {
+  small_primes: Vec<u32>,
+  // Numbers left on the sieve from this one are tested with is_prime
+  bound: u64,
+  sieve: Vec<u64>,
+  segment_size: usize,
+  end: usize,
+  // Start of the next segment to sieve, or None when there are no more
+  next_low: Option<usize>,
+  // Primes found and not returned yet, and the position of the next one
+  found: Vec<usize>,
+  position: usize,
+}
+
+impl PrimeIter {
+
+  pub fn new(start: usize, end: usize) -> PrimeIter {
+    PrimeIter::try_new(start, end).unwrap_or_else(|err| panic!("{err}"))
+  }
+
+  // Create the iterator like new, or get an error if there isn't memory for the primes
+  // to sieve the range with (the ones up to the square root of end, up to 1 GB)
+  pub fn try_new(start: usize, end: usize) -> Result<PrimeIter> {
+
+    let start: usize = std::cmp::max(start, 2);
+    // Narrow ranges near the top are sieved with few primes, like in count_primes
+    let (sieve_limit, bound): (u32, u64) = sieve_limit(start, end);
+    let small_primes: Vec<u32> = try_simple_sieve(sieve_limit)?;
+
+    // The small primes are crossed out when sieving, so return them first
+    let found: Vec<usize> = small_primes.iter()
+      .map(|&prime| prime as usize)
+      .filter(|&prime| prime >= start && prime <= end)
+      .collect();
+
+    // Segments of the L2 size, like the ones of count_primes
+    let segment_size: usize = if start > end { wheel::WHEEL } else {
+      std::cmp::min(cpu::get_cache_info().l2_size() * wheel::WHEEL, end - start + 1).div_ceil(wheel::WHEEL) * wheel::WHEEL
+    };
+
+    Ok(PrimeIter {
+      small_primes,
+      bound,
+      sieve: try_vec(u64::MAX, wheel::words::<u64>(segment_size / wheel::WHEEL + 1))?,
+      segment_size,
+      end,
+      next_low: if start > end { None } else { Some(start) },
+      found,
+      position: 0,
+    })
+  }
+
+  // Sieve the next segment, storing the primes found. Returns false if there are no more segments
+  fn sieve_next_segment(&mut self) -> bool {
+
+    let low: usize = match self.next_low {
+      Some(low) => low,
+      None => return false,
+    };
+    let high: usize = std::cmp::min(low.saturating_add(self.segment_size - 1), self.end);
+    self.next_low = if high >= self.end { None } else { Some(high + 1) };
+
+    self.found.clear();
+    self.position = 0;
+    segment_primes(&mut self.sieve, &self.small_primes, low, high, &mut self.found);
+    if self.bound != u64::MAX {
+      let bound: u64 = self.bound;
+      self.found.retain(|&num| (num as u64) < bound || is_prime(num as u64));
+    }
+
+    true
+  }
+}
+
+impl Iterator for PrimeIter {
+  type Item = usize;
+
+  fn next(&mut self) -> Option<usize> {
+
+    // Sieve segments until one has primes or there are no more
+    while self.position >= self.found.len() {
+      if !self.sieve_next_segment() { return None }
+    }
+
+    self.position += 1;
+    Some(self.found[self.position - 1])
+  }
+}
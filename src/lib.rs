@@ -1,37 +1,88 @@
+use std::collections::TryReserveError;
+use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, OnceLock};
 use std::thread;
+use std::time::{Duration, Instant};
 
 mod bits;
+mod bitsieve;
+mod bucket;
+mod checkpoint;
+mod config;
+mod coordinator;
 mod cpu;
+mod error;
+mod factor;
+mod iter;
+mod lmo;
+mod next;
+mod nth;
+mod presieve;
+mod primality;
+mod progress;
+mod shard;
+mod table;
+mod tune;
+mod wheel;
+
+pub use bitsieve::{BitSieve, BitSieveIter};
+pub use config::{Algorithm, ConfigError, SieveConfig, LMO_THRESHOLD};
+pub use coordinator::{run_worker, serve_coordinator, CoordinatorOptions};
+pub use error::{Error, Result};
+pub use cpu::{get_cache_info, get_thread_count, Cache, CacheInfo, ThreadCount, ThreadSource, THREADS_VARIABLE};
+pub use factor::Factorization;
+pub use iter::PrimeIter;
+pub use lmo::count_primes_lmo;
+pub use next::{next_prime, next_prime_u128, prev_prime, prev_prime_u128, try_next_prime};
+pub use nth::{nth_prime, try_nth_prime};
+pub use progress::{CancelToken, CountOptions, CountResult, Progress, ProgressCallback};
+pub use shard::{merge_counts, PartialCount, Shard};
+pub use table::PrimeTable;
+pub use tune::{machine_name, profile_path, tune, Profile, Trial, TuneOptions, TuneResult, PROFILE_VARIABLE};
 
 // Check if a number is prime
 pub fn is_prime(num: u64) -> bool {
 
-  // If num is 2, then it is prime
-  if num == 2 { return true }
-  // If num is less than 2 or is even, then it isn't prime
-  if num < 2 || num % 2 == 0 { return false }
+  // If num is less than 2, then it isn't prime
+  if num < 2 { return false }
 
-  // For each odd number between 3 and the square root of num,
-  // if it's a divisor of num then num isn't prime
-  let mut i: u64 = 3;
-  while i < (num as f64).sqrt() as u64 {
-    
-    if num % i == 0 {
-      return false;
-    }
+  // Trial divide by the small primes first, which quickly
+  // discards most composite numbers
+  for &prime in primality::SMALL_PRIMES.iter() {
+    if num == prime { return true }
+    if num.is_multiple_of(prime) { return false }
+  }
 
-    i += 2;
+  // If num has no factor up to its square root, then it is prime
+  let last: u64 = primality::SMALL_PRIMES[primality::SMALL_PRIMES.len() - 1];
+  if num < last * last { return true }
+
+  // Else use the Miller-Rabin test, which is deterministic for any u64
+  primality::miller_rabin(num)
+}
+
+// Check if a 128 bit number is prime
+pub fn is_prime_u128(num: u128) -> bool {
+
+  // If num fits in 64 bits, use the deterministic test
+  if num <= u64::MAX as u128 { return is_prime(num as u64) }
+
+  // Discard the numbers with small factors
+  for &prime in primality::SMALL_PRIMES.iter() {
+    if num.is_multiple_of(prime as u128) { return false }
   }
 
-  // Else num is prime
-  true
+  // Else use the Baillie-PSW test
+  primality::baillie_psw(num)
 }
 
 // Split a number into its prime factors
 pub fn split_into_factors(num: u64) -> Vec<u64> {
 
+  // Numbers below this are removed by trial division before using Pollard's rho
+  const TRIAL_LIMIT: u64 = 1 << 10;
+
   // Duplicate num as mutable
   let mut num: u64 = num;
   // Create a vector to store the factors
@@ -41,35 +92,98 @@ pub fn split_into_factors(num: u64) -> Vec<u64> {
 
   // First, while the number is divisible by two,
   // divide it by two and push 2 to the vector
-  while num % 2 == 0 {
+  while num.is_multiple_of(2) {
     factors.push(2);
     num /= 2;
   }
 
-  // Then, for each odd number between 3 and the square root of the current num,
+  // Then, for each small odd number up to the square root of the current num,
   // if it's a divisor of num divide num by that number and add the number to the vector
   let mut i: u64 = 3;
-  let mut num_sqrt: u64 = (num as f64).sqrt() as u64;
-  while i <= num_sqrt {
+  while i < TRIAL_LIMIT && i * i <= num {
 
-    while num % i == 0 {
+    while num.is_multiple_of(i) {
       factors.push(i);
       num /= i;
-      num_sqrt = (num as f64).sqrt() as u64;
     }
 
     i += 2;
   }
 
-  // If num is a prime number add itself to the factors
-  if num > 1 {
+  // If num has no factors up to its square root, it is a prime number,
+  // else split the remaining part with Pollard's rho
+  if num > 1 && i * i > num {
     factors.push(num);
   }
+  else {
+    factor::split_composite(num, &mut factors);
+    factors.sort_unstable();
+  }
 
   // Return the vector
   factors
 }
 
+// Split a number into its prime factors, grouped with their exponents
+pub fn factorize(num: u64) -> Factorization {
+  Factorization::group(split_into_factors(num))
+}
+
+// Split a number into its prime factors like split_into_factors, returning an error for 0
+// instead of no factors (which is the factorization of 1)
+pub fn try_split_into_factors(num: u64) -> Result<Vec<u64>> {
+  if num == 0 { return Err(Error::Zero) }
+  Ok(split_into_factors(num))
+}
+
+// Split a number into its prime factors like factorize, returning an error for 0
+pub fn try_factorize(num: u64) -> Result<Factorization> {
+  try_split_into_factors(num).map(Factorization::group)
+}
+
+// Get all the divisors of a number, sorted. 0 has no divisors listed
+pub fn divisors(num: u64) -> Vec<u64> {
+  if num == 0 { return Vec::new() }
+  factorize(num).divisors()
+}
+
+// Number of divisors of a number, τ(n). Returns 0 for 0
+pub fn divisor_count(num: u64) -> u64 {
+  if num == 0 { return 0 }
+  factorize(num).divisor_count()
+}
+
+// Sum of the k-th powers of the divisors of a number, σ_k(n).
+// Returns None if it doesn't fit in 128 bits, and 0 for 0
+pub fn divisor_sum(num: u64, k: u32) -> Option<u128> {
+  if num == 0 { return Some(0) }
+  factorize(num).divisor_sum(k)
+}
+
+// Euler's totient function, φ(n). Returns 0 for 0
+pub fn totient(num: u64) -> u64 {
+  if num == 0 { return 0 }
+  factorize(num).totient()
+}
+
+// Möbius function, μ(n). Returns 0 for 0
+pub fn mobius(num: u64) -> i8 {
+  if num == 0 { return 0 }
+  factorize(num).mobius()
+}
+
+// Radical of a number, rad(n). Returns 0 for 0
+pub fn radical(num: u64) -> u64 {
+  if num == 0 { return 0 }
+  factorize(num).radical()
+}
+
+// Carmichael's function, λ(n). Returns 0 for 0
+pub fn carmichael(num: u64) -> u64 {
+  if num == 0 { return 0 }
+  factorize(num).carmichael()
+}
+
 // Greatest Common Divisor
 pub fn gcd(x: u64, y: u64) -> u64 {
 
@@ -96,166 +210,492 @@ pub fn lcm(x: u64, y: u64) -> u128 {
   x as u128 * ((y as u128) / (gcd(x, y) as u128))
 }
 
+// Error of an allocation that failed, so it is returned instead of aborting
+fn out_of_memory(_: TryReserveError) -> Error {
+  Error::OutOfMemory
+}
+
+// Create a vector with len copies of value, or an error if there isn't memory
+// for it. The sieves of wide ranges can need more memory than there is
+pub(crate) fn try_vec<T: Clone>(value: T, len: usize) -> Result<Vec<T>> {
+  let mut vec: Vec<T> = Vec::new();
+  vec.try_reserve_exact(len).map_err(out_of_memory)?;
+  vec.resize(len, value);
+  Ok(vec)
+}
+
 // Initial sieve
 fn simple_sieve(size: u32) -> Vec<u32> {
+  try_simple_sieve(size).unwrap_or_else(|err| panic!("{err}"))
+}
 
-  // Create a vector to store all prime numbers found
-  let mut primes: Vec<u32> = Vec::new();
+// Initial sieve like simple_sieve, or an error if there isn't memory for it (up to 1 GB near 2^32)
+fn try_simple_sieve(size: u32) -> Result<Vec<u32>> {
 
-  // Handle specific scenarios 
-  if size >= 2 { primes.push(2) }
-  if size <= 2 { return primes }
+  // Create a vector to store all prime numbers found,
+  // reserving enough space for them as π(x) < 1.26 * x / ln(x)
+  let mut primes: Vec<u32> = Vec::new();
+  primes.try_reserve_exact(if size < 17 { 7 } else { (1.26 * size as f64 / (size as f64).ln()) as usize }).map_err(out_of_memory)?;
 
-  // Create the sieve
-  let mut sieve: Vec<u8> = vec![0xff; ((size + 13) / 16) as usize];
-  // Set the last bits that doesn't have to be sieved to not prime
-  if sieve.len() != 0 {
-    bits::unset_last_bits(sieve.last_mut().unwrap(), (7 - ((size - 3) / 2) % 8) as u8);
+  // Handle specific scenarios, as the primes of the wheel are not on the sieve
+  for prime in wheel::WHEEL_PRIMES {
+    if prime <= size { primes.push(prime) }
   }
+  if size < 7 { return Ok(primes) }
+
+  // Create the sieve, where byte i holds the numbers 30 * i + r with r coprime to 30
+  let size: usize = size as usize;
+  let mut sieve: Vec<u64> = try_vec(u64::MAX, wheel::words::<u64>(size / wheel::WHEEL + 1))?;
+  // 1 isn't prime, and the last bits don't have to be sieved
+  bits::unset_bit(&mut sieve[0], 0);
+  wheel::unset_above(&mut sieve, size / wheel::WHEEL, size % wheel::WHEEL);
 
   for i in 0..sieve.len() {
-    for bit in 0..8 {
+    for bit in 0..u64::BITS {
 
       // If the bit corresponding to this number is unset,
       // it is composite, so continue.
-      if bits::is_bit_unset(&sieve[i as usize], bit) { continue }
+      if bits::is_bit_unset(&sieve[i], bit) { continue }
 
       // If it is prime, add it to the vector
-      let i: u32 = (i*16) as u32 + (bit*2) as u32 + 3;
-      primes.push(i);
-
-      // If i to the power of 2 is greater than the last element,
-      // i can't be divisor of any of the remaining numbers, so continue.
-      if (i as u64).pow(2) > size as u64 { continue }
-
-      // Unset the bit corresponding to all multiples of i
-      let mut multiple: u32 = i.pow(2);
-      while multiple <= size {
-        bits::unset_bit(&mut sieve[((multiple - 3) / 16) as usize], (((multiple - 3) / 2) % 8) as u8);
-        // Add 2 times i because even numbers are not on the sieve
-        multiple += 2*i;
-      }
+      let prime: usize = wheel::number::<u64>(0, i, bit);
+      primes.push(prime as u32);
+
+      // If it to the power of 2 is greater than the last element,
+      // it can't be divisor of any of the remaining numbers, so continue.
+      if prime * prime > size { continue }
 
+      // Unset the bit corresponding to all multiples of it coprime to 30
+      wheel::cross_off(&mut sieve, 0, size, prime, prime);
     }
   }
 
-  primes
+  Ok(primes)
 }
 
-// Sieve a segment
-fn segment_sieve(sieve: &mut Vec<u8>, primes: &Vec<u32>, low: usize, high: usize) -> u32 {
+// Sieve the numbers of a segment coprime to 30. After it, the bit of every one of them
+// between low and high is set only if it has no divisor in primes (so the primes
+// themselves are unset). Returns the multiple of 30 where the sieve starts
+// and how many words of it were used, or None if there are no such numbers. The sieve
+// grows if the segment doesn't fit in it
+fn mark_segment(sieve: &mut Vec<u64>, primes: &[u32], low: usize, high: usize) -> Option<(usize, usize)> {
 
-  // Handle specific scenarios
-  if low == 2 && high == 2 { return 1 }
-  else if low > high { return 0 }
-  else if low == high && low % 2 == 0 { return 0 }
+  // Skip 1, which isn't prime, and the primes of the wheel
+  if high < 7 { return None }
+  let low: usize = std::cmp::max(low, 7);
+  if low > high { return None }
 
-  // Update low and high to be odd numbers
-  let size: usize = std::cmp::max((high - low).div_ceil(16), 1);
-  let low: usize = if low < 3 { 3 } else if low == high { low } else if low % 2 == 0 { low + 1 } else { low };
-  let high: usize = if low == high { high } else if high % 2 == 0 { high - 1 } else { high };
+  // Fill the sieve, copying the multiples of the presieve primes already crossed off
+  // if they are all in primes (else they would be crossed off when they shouldn't)
+  let base: usize = low - low % wheel::WHEEL;
+  let size: usize = (high - base) / wheel::WHEEL + 1;
+  let words: usize = wheel::words::<u64>(size);
+  if words > sieve.len() { sieve.resize(words, u64::MAX) }
+  let presieved: bool = presieve::covers(primes);
+  if presieved { presieve::fill(&mut sieve[..words], base) } else { sieve[..words].fill(u64::MAX) }
+  let first_prime: u32 = if presieved { presieve::PRESIEVE_PRIMES[presieve::PRESIEVE_PRIMES.len() - 1] + 1 } else { 7 };
 
-  // Fill the sieve and reset the count
-  assert!(size <= sieve.len());
-  sieve.fill(0xff);
-  let mut count: u32 = 0;
+  // Set the bits that don't have to be sieved to not prime
+  wheel::unset_below(sieve, low - base);
+  wheel::unset_above(sieve, size - 1, (high - base) % wheel::WHEEL);
 
-  // Set the last bits that doesn't have to be sieved to not prime
-  bits::unset_last_bits(&mut sieve[size - 1], (7 - ((high - low) / 2) % 8) as u8);
-
-  // For each prime (skipping the 2, which is not needed on odd numbers)
-  for prime in primes.iter().skip(1) {
-    // Get the first multiple
-    let mut multiple: usize = low.div_ceil(*prime as usize) * (*prime as usize);
-    if multiple % 2 == 0 { multiple += *prime as usize }
-    
+  // For each prime (skipping the ones of the wheel, which have no multiples on the sieve,
+  // and the presieved ones)
+  for &prime in primes.iter().filter(|&&prime| prime >= first_prime) {
+    let prime: usize = prime as usize;
     // Mark all multiples as not primes
-    while multiple <= high {
-      bits::unset_bit(&mut sieve[(multiple - low) / 16], (((multiple - low) / 2) % 8) as u8);
-      multiple += 2 * (*prime as usize);
-    }
+    wheel::cross_off(sieve, base, high, prime, low.div_ceil(prime));
   }
 
+  Some((base, words))
+}
+
+// Count the primes of the wheel between low and high that are not in primes
+fn count_wheel_primes(primes: &[u32], low: usize, high: usize) -> u32 {
+  wheel::WHEEL_PRIMES.iter()
+    .filter(|&&prime| low <= prime as usize && prime as usize <= high && !primes.contains(&prime))
+    .count() as u32
+}
+
+// Sieve a segment, counting the primes between low and high that are not in primes
+fn segment_sieve(sieve: &mut Vec<u64>, primes: &[u32], low: usize, high: usize) -> u32 {
+
+  // 2, 3 and 5 are not on the sieve
+  let mut count: u32 = count_wheel_primes(primes, low, high);
+
   // Count how many primes are there in this segment
-  for byte in sieve.iter().take(size) {
-    count += bits::count_set_bits(&byte) as u32;
+  if let Some((_, words)) = mark_segment(sieve, primes, low, high) {
+    for word in sieve.iter().take(words) {
+      count += bits::count_set_bits(word);
+    }
   }
 
   count
 }
 
-// Count the number of prime numbers below or equal to limit
-pub fn count_primes(limit: usize, start: Option<usize>, threads: Option<usize>, cache: Option<usize>) -> usize {
-
-  if limit < 2 { return 0 }
+// Sieve a segment, pushing to found the primes between low and high that are not in primes
+fn segment_primes(sieve: &mut Vec<u64>, primes: &[u32], low: usize, high: usize, found: &mut Vec<usize>) {
 
-  let threads: usize = threads.unwrap_or(cpu::get_cores());
-  let cache: usize = cache.unwrap_or(cpu::get_cache_size());
-  let start: usize = std::cmp::max(start.unwrap_or(2), 2);
-  
-  let sqrt: u32 = (limit as f64).sqrt() as u32;
-  let segment_size: usize = std::cmp::min(std::cmp::max(sqrt as usize, cache * 16), limit - std::cmp::max(sqrt as usize, start - 1)).div_ceil(16) * 16;
-  let segment_sieve_size: usize = segment_size.div_ceil(16);
-  
-  let small_primes: Arc<Vec<u32>> = Arc::new(simple_sieve(sqrt));
-  let count: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(small_primes.len()));
-  let iter: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+  // 2, 3 and 5 are not on the sieve
+  for prime in wheel::WHEEL_PRIMES {
+    if low <= prime as usize && prime as usize <= high && !primes.contains(&prime) {
+      found.push(prime as usize);
+    }
+  }
 
-  if start > 2 {
-    count.fetch_sub(
-      if start > sqrt as usize {
-        small_primes.len()
+  // Each set bit is a prime
+  if let Some((base, words)) = mark_segment(sieve, primes, low, high) {
+    for (i, &word) in sieve.iter().take(words).enumerate() {
+      let mut word: u64 = word;
+      while word != 0 {
+        found.push(wheel::number::<u64>(base, i, word.trailing_zeros()));
+        word &= word - 1;
       }
-      else {
-        small_primes.iter().filter(|&&x| (x as usize) < start).count()
-      },
-    Ordering::Relaxed);
+    }
+  }
+}
+
+// Iterate over the prime numbers between start and end (both included)
+pub fn primes_in_range(start: usize, end: usize) -> PrimeIter {
+  PrimeIter::new(start, end)
+}
+
+// Iterate over the prime numbers between start and end like primes_in_range, or
+// get an error if there isn't memory for the primes to sieve them with
+pub fn try_primes_in_range(start: usize, end: usize) -> Result<PrimeIter> {
+  PrimeIter::try_new(start, end)
+}
+
+// Count the number of prime numbers below or equal to x, π(x)
+pub fn prime_pi(x: usize) -> usize {
+  count_primes_lmo(x)
+}
+
+// Primes used to sieve ranges narrower than the square root of their limit
+const NARROW_SIEVE_LIMIT: u32 = 1 << 16;
+// Each thread sieves chunks of at least this many times the greatest sieving prime (the
+// square root of the limit if the range isn't narrow), so filling the buckets of the large
+// primes costs little compared to sieving, unless there are more threads than chunks
+const CHUNK_SQRTS: usize = 16;
+// Time between the saves of the checkpoint of count_primes_resume
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+// Get the primes to sieve the numbers between start and limit with, up to the returned limit,
+// and the bound from which the numbers left on the sieve must be tested with is_prime
+// (u64::MAX if none must). If the range is narrower than the square root of the limit,
+// getting all the primes up to the square root costs more than the range itself (up to
+// 2^32 near the top of the range), so only the smaller ones are used
+fn sieve_limit(start: usize, limit: usize) -> (u32, u64) {
+  let sqrt: u32 = limit.isqrt() as u32;
+  if limit.saturating_sub(start) < sqrt as usize && sqrt > NARROW_SIEVE_LIMIT {
+    // Numbers without factors lower than the square of the last prime are primes
+    (NARROW_SIEVE_LIMIT, (NARROW_SIEVE_LIMIT as u64).pow(2))
   }
+  else {
+    (sqrt, u64::MAX)
+  }
+}
+
+// Count the bits set in a sieve, testing the numbers not lower than bound
+fn count_sieve<W: bits::Word>(sieve: &[W], base: usize, bound: u64) -> usize {
+
+  let mut count: usize = 0;
+
+  if bound == u64::MAX {
+    return sieve.iter().map(|word| bits::count_set_bits(word) as usize).sum();
+  }
+
+  // Numbers in each word
+  let span: usize = wheel::bytes_per_word::<W>() * wheel::WHEEL;
+
+  for (i, &word) in sieve.iter().enumerate() {
+    // Below the bound, every set bit is a prime
+    if ((base + i * span) as u64) < bound.saturating_sub(span as u64) {
+      count += bits::count_set_bits(&word) as usize;
+      continue
+    }
+
+    let mut word: W = word;
+    while word != W::ZERO {
+      let bit: u32 = word.trailing_zeros();
+      let num: u64 = wheel::number::<W>(base, i, bit) as u64;
+      if num < bound || is_prime(num) { count += 1 }
+      bits::unset_bit(&mut word, bit);
+    }
+  }
+
+  count
+}
+
+// Threads and cache of a count when they are not set. They are read from files, so they are only looked up once
+fn defaults() -> (usize, usize) {
+
+  static DEFAULTS: OnceLock<(usize, usize)> = OnceLock::new();
+  *DEFAULTS.get_or_init(|| {
+    let count: cpu::ThreadCount = cpu::get_thread_count();
+    // The tests don't read the profile, so the one of the machine running them doesn't change their counts
+    let profile: Option<tune::Profile> = if cfg!(test) { None } else { tune::Profile::for_machine() };
+
+    // PRIMEUTILS_THREADS, else the threads of the profile of this machine (if it can run that many),
+    // else the ones the machine can run
+    let threads: usize = match &profile {
+      Some(profile) if count.source != cpu::ThreadSource::Env => std::cmp::min(profile.threads, count.threads),
+      _ => count.threads,
+    };
+    // The cache of the profile, else the L1 size
+    let cache: usize = profile.map_or_else(cpu::get_cache_size, |profile| profile.cache);
+    (threads, cache)
+  })
+}
+
+fn default_threads() -> usize {
+  defaults().0
+}
+
+fn default_cache() -> usize {
+  defaults().1
+}
+
+// Numbers of each block of count_primes, which fit in L1 (rounded down to a power of two)
+// as the small primes are crossed off one block at a time
+fn segment_size(cache: usize) -> usize {
+  (1_usize << std::cmp::max(cache, 1).ilog2()).saturating_mul(wheel::WHEEL)
+}
+
+// Numbers of the chunks between start and limit that the threads of count_primes take one at a time
+fn chunk_size(start: usize, limit: usize, threads: usize, cache: usize) -> usize {
+  // Whole blocks, of the greatest sieving prime times CHUNK_SQRTS, but few enough
+  // for every thread to get one even if the range is narrow
+  let segment_size: usize = segment_size(cache);
+  let sieve_limit: usize = sieve_limit(start, limit).0 as usize;
+  let size: usize = std::cmp::min(sieve_limit.saturating_mul(CHUNK_SQRTS), (limit - start) / std::cmp::max(threads, 1));
+  segment_size.saturating_mul(std::cmp::max(size.div_ceil(segment_size), 1))
+}
+
+// Count the primes in the chunks of a checkpoint not done yet, which the threads take in order.
+// The count of each chunk is passed to on_chunk (in the calling thread) as soon as it is sieved,
+// so they may come out of order. If cancel is cancelled, the threads stop between segments, and
+// the chunks left unfinished are not passed. Returns the primes used to sieve, which are
+// not counted in the chunks. The small primes are crossed off in blocks of the L1 size in cache,
+// and the segments are sized for L2. Returns an error if a thread panicked
+fn count_chunks<F: FnMut(usize, usize)>(plan: checkpoint::Checkpoint, threads: usize, cache: usize, cancel: Option<CancelToken>, mut on_chunk: F) -> Result<Vec<u32>> {
+
+  let (start, limit, chunk_size): (usize, usize, usize) = (plan.start, plan.limit, plan.chunk_size);
+
+  let (sieve_limit, bound): (u32, u64) = sieve_limit(start, limit);
+  let small_primes: Arc<Vec<u32>> = Arc::new(simple_sieve(sieve_limit));
+  let segment_bytes: usize = std::cmp::max(cpu::get_cache_info().l2_size(), cache);
+  let done: Arc<checkpoint::Chunks> = Arc::new(plan.done);
+  let iter: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+  let (sender, receiver) = mpsc::channel::<(usize, usize)>();
 
   let mut handles = vec![];
 
   for _ in 0..threads {
 
-    let mut sieve: Vec<u8> = vec![0xff; segment_sieve_size];
-    let count: Arc<AtomicUsize> = Arc::clone(&count);
     let iter: Arc<AtomicUsize> = Arc::clone(&iter);
     let small_primes: Arc<Vec<u32>> = Arc::clone(&small_primes);
+    let done: Arc<checkpoint::Chunks> = Arc::clone(&done);
+    let cancel: Option<CancelToken> = cancel.clone();
+    let sender: mpsc::Sender<(usize, usize)> = sender.clone();
 
     let handle = thread::spawn(move || {
 
+      let cancelled = || cancel.as_ref().is_some_and(|cancel| cancel.is_cancelled());
       let mut low: usize;
       let mut high: usize;
+      // Reset for each chunk, so the multiples of the large primes are only allocated once
+      let mut sieve: bucket::BucketSieve<u64> = bucket::BucketSieve::new(&small_primes, 1, 0, cache, segment_bytes);
 
-      loop {
+      'chunks: loop {
         let i: usize = iter.fetch_add(1, Ordering::AcqRel);
 
-        low = start + (i * segment_size);
-        if low > limit {
-          break;
-        }
-        high = std::cmp::min(low + segment_size - 1, limit);
+        // Stop when past the limit, even if it overflows
+        low = match i.checked_mul(chunk_size).and_then(|offset| start.checked_add(offset)) {
+          Some(low) if low <= limit => low,
+          _ => break,
+        };
+        high = std::cmp::min(low.saturating_add(chunk_size - 1), limit);
+        if done.contains(i) { continue }
+
+        // 2, 3 and 5 are not on the sieve
+        let mut current_count: usize = count_wheel_primes(&small_primes, low, high) as usize;
 
-        let current_count = segment_sieve(&mut sieve, &small_primes, low, high) as usize;
+        // Sieve the segments of the chunk in order, so the buckets can be used
+        sieve.reset(&small_primes, low, high);
+        while let Some((base, segment)) = sieve.next_segment() {
+          if cancelled() { break 'chunks }
+          current_count += count_sieve(segment, base, bound);
+        }
 
-        count.fetch_add(current_count, Ordering::Relaxed);
+        // The receiver only goes away if the calling thread panicked
+        if sender.send((i, current_count)).is_err() { break }
       }
     });
 
     handles.push(handle);
   }
 
-  for handle in handles {
-    handle.join().unwrap();
+  // The loop ends when every thread has finished and dropped its sender
+  drop(sender);
+  for (chunk, count) in receiver {
+    on_chunk(chunk, count);
+  }
+
+  join_threads(handles)?;
+  // Every clone was dropped with its thread, even if it panicked
+  Ok(Arc::into_inner(small_primes).unwrap_or_default())
+}
+
+// Wait for every thread, returning an error if any of them panicked
+fn join_threads(handles: Vec<thread::JoinHandle<()>>) -> Result<()> {
+  let panicked: usize = handles.into_iter().filter_map(|handle| handle.join().err()).count();
+  if panicked > 0 { return Err(Error::ThreadPanicked) }
+  Ok(())
+}
+
+// Count the primes of the chunks of a checkpoint not done yet, adding them to it and
+// calling on_chunk after each one. Returns the primes in the part of the range covered
+fn run_count<F: FnMut(&checkpoint::Checkpoint)>(checkpoint: &mut checkpoint::Checkpoint, options: &mut CountOptions, mut on_chunk: F) -> Result<CountResult> {
+
+  let now: Instant = Instant::now();
+  let threads: usize = options.threads.unwrap_or_else(default_threads);
+  let cache: usize = options.cache.unwrap_or_else(default_cache);
+  let total: usize = checkpoint.chunks();
+
+  let small_primes: Vec<u32> = count_chunks(checkpoint.clone(), threads, cache, options.cancel.clone(), |chunk, count| {
+    checkpoint.add(chunk, count);
+    if let Some(progress) = options.progress.as_mut() {
+      progress(&Progress { done: checkpoint.done.len(), total, primes: checkpoint.done.primes(), elapsed: now.elapsed() });
+    }
+    on_chunk(checkpoint);
+  })?;
+
+  // Only the chunks from start without gaps are in the result, with the primes used to sieve in them
+  let (end, primes): (usize, usize) = checkpoint.covered();
+  let sieving: usize = small_primes.iter().filter(|&&prime| checkpoint.start <= prime as usize && prime as usize <= end).count();
+
+  Ok(CountResult {
+    primes: primes + sieving,
+    start: checkpoint.start,
+    end,
+    cancelled: end < checkpoint.limit,
+  })
+}
+
+// Count the number of prime numbers below or equal to limit with the sieve. A threads or cache
+// of 0 uses the default one, like when it is None. Panics if a thread panics, try_count_primes
+// returns an error instead (and for a threads or cache of 0)
+pub fn count_primes(limit: usize, start: Option<usize>, threads: Option<usize>, cache: Option<usize>) -> usize {
+  let threads: Option<usize> = threads.filter(|&threads| threads > 0);
+  let cache: Option<usize> = cache.filter(|&cache| cache > 0);
+  try_count_primes(limit, start, threads, cache).unwrap_or_else(|err| panic!("{err}"))
+}
+
+// Count the number of prime numbers below or equal to limit like count_primes, or get why it can't
+pub fn try_count_primes(limit: usize, start: Option<usize>, threads: Option<usize>, cache: Option<usize>) -> Result<usize> {
+  let mut config: SieveConfig = SieveConfig::new(limit).algorithm(Algorithm::Sieve);
+  if let Some(start) = start { config = config.start(start) }
+  if let Some(threads) = threads { config = config.threads(threads) }
+  if let Some(cache) = cache { config = config.segment_bytes(cache) }
+  config.count().map(|result| result.primes)
+}
+
+// Count the number of prime numbers below or equal to limit like count_primes, reporting
+// the progress to a callback and stopping early if the cancel token is cancelled. Then
+// the result has the primes of the part of the range covered. Returns an error if threads or
+// cache is 0, or if a thread panics
+pub fn count_primes_with(limit: usize, start: Option<usize>, options: CountOptions) -> Result<CountResult> {
+
+  config::check_sieve(options.threads, options.cache)?;
+  let start: usize = std::cmp::max(start.unwrap_or(2), 2);
+  if limit < 2 || start > limit { return Ok(CountResult { primes: 0, start, end: limit, cancelled: false }) }
+
+  let mut options: CountOptions = options;
+  let threads: usize = options.threads.unwrap_or_else(default_threads);
+  let cache: usize = options.cache.unwrap_or_else(default_cache);
+  let mut checkpoint: checkpoint::Checkpoint = checkpoint::Checkpoint::new(start, limit, chunk_size(start, limit, threads, cache));
+
+  run_count(&mut checkpoint, &mut options, |_| {})
+}
+
+// Count the number of prime numbers below or equal to limit like count_primes_with, saving the
+// progress to a checkpoint file every CHECKPOINT_INTERVAL and when it finishes or is cancelled.
+// If the file exists, the count is resumed from it, skipping the chunks already sieved. The
+// checkpoint must be of the same start and limit, else Error::CheckpointMismatch is returned
+pub fn count_primes_resume(limit: usize, start: Option<usize>, options: CountOptions, path: &Path) -> Result<CountResult> {
+
+  config::check_sieve(options.threads, options.cache)?;
+  let start: usize = std::cmp::max(start.unwrap_or(2), 2);
+  if limit < 2 || start > limit { return Ok(CountResult { primes: 0, start, end: limit, cancelled: false }) }
+
+  let mut options: CountOptions = options;
+  let threads: usize = options.threads.unwrap_or_else(default_threads);
+  let cache: usize = options.cache.unwrap_or_else(default_cache);
+
+  // The chunks of the checkpoint are kept, even if the threads or the cache are different now
+  let mut checkpoint: checkpoint::Checkpoint = if path.exists() {
+    let checkpoint: checkpoint::Checkpoint = checkpoint::Checkpoint::load(path)?;
+    if checkpoint.start != start || checkpoint.limit != limit {
+      return Err(Error::CheckpointMismatch { start: checkpoint.start, limit: checkpoint.limit });
+    }
+    checkpoint
   }
+  else {
+    checkpoint::Checkpoint::new(start, limit, chunk_size(start, limit, threads, cache))
+  };
+
+  // Save it before sieving, so a file that can't be written fails early
+  checkpoint.save(path)?;
+  let mut last_save: Instant = Instant::now();
+
+  // A failed save is retried at the next interval, and only the last one is returned
+  let result: CountResult = run_count(&mut checkpoint, &mut options, |checkpoint| {
+    if last_save.elapsed() >= CHECKPOINT_INTERVAL {
+      let _ = checkpoint.save(path);
+      last_save = Instant::now();
+    }
+  })?;
+
+  checkpoint.save(path)?;
+  Ok(result)
+}
+
+// Count the prime numbers of a shard of the range between start and limit, so the count can be
+// split across processes or machines and merged with merge_counts. If a checkpoint file is given,
+// the count of the shard is resumed from it like count_primes_resume. If the count is cancelled,
+// the range of the result ends where it stopped. Returns Error::TooManyShards if there are
+// more shards than numbers in the range
+pub fn count_primes_shard(limit: usize, start: Option<usize>, shard: Shard, options: CountOptions, checkpoint: Option<&Path>) -> Result<PartialCount> {
+
+  let start: usize = std::cmp::max(start.unwrap_or(2), 2);
+  let (low, high): (usize, usize) = shard.range(start, limit)
+    .ok_or(Error::TooManyShards { start, limit, shards: shard.count() })?;
+
+  let segment_size: usize = segment_size(options.cache.unwrap_or_else(default_cache));
+  let result: CountResult = match checkpoint {
+    Some(path) => count_primes_resume(high, Some(low), options, path)?,
+    None => count_primes_with(high, Some(low), options)?,
+  };
 
-  count.load(Ordering::SeqCst)
+  Ok(PartialCount {
+    shard,
+    start,
+    limit,
+    low,
+    high: result.end,
+    primes: result.primes,
+    segment_size,
+  })
 }
 
 #[cfg(test)]
 mod tests {
   use crate::*;
+  use crate::bits::Word;
 
   #[test]
+  #[allow(clippy::bool_assert_comparison)]
   fn test_is_prime() {
     assert_eq!(is_prime(0), false);
     assert_eq!(is_prime(1), false);
@@ -277,6 +717,45 @@ mod tests {
     assert_eq!(is_prime(4_294_967_296), false);
     assert_eq!(is_prime(18_446_744_073_709_551_614), false);
     assert_eq!(is_prime(18_446_744_073_709_551_615), false);
+    // Strong pseudoprimes to several bases
+    assert_eq!(is_prime(3_215_031_751), false);
+    assert_eq!(is_prime(3_825_123_056_546_413_051), false);
+    // Large primes
+    assert_eq!(is_prime(1_000_000_007), true);
+    assert_eq!(is_prime(4_294_967_311), true);
+    assert_eq!(is_prime(9_223_372_036_854_775_783), true);
+    assert_eq!(is_prime(18_446_744_073_709_551_557), true);
+    assert_eq!(is_prime(18_446_744_073_709_551_559), false);
+    // Compare with the sieve for every small number
+    let primes: Vec<u32> = simple_sieve(100_000);
+    assert_eq!((0..=100_000).filter(|&x| is_prime(x)).count(), primes.len());
+    assert!(primes.iter().all(|&x| is_prime(x as u64)));
+  }
+
+  #[test]
+  fn test_is_prime_u128() {
+    assert!(!is_prime_u128(0));
+    assert!(!is_prime_u128(1));
+    assert!(is_prime_u128(2));
+    assert!(is_prime_u128(18_446_744_073_709_551_557));
+    assert!(!is_prime_u128(18_446_744_073_709_551_615));
+    assert!(!is_prime_u128(18_446_744_073_709_551_616));
+    assert!(is_prime_u128(18_446_744_073_709_551_629));
+    assert!(is_prime_u128((1 << 89) - 1));
+    assert!(is_prime_u128((1 << 127) - 1));
+    assert!(is_prime_u128(u128::MAX - 158));
+    assert!(!is_prime_u128(u128::MAX));
+    // Perfect square of a prime
+    assert!(!is_prime_u128(((1 << 61) - 1) * ((1 << 61) - 1)));
+    // Product of two large primes
+    assert!(!is_prime_u128(((1 << 61) - 1) * ((1 << 31) - 1)));
+    assert!(!is_prime_u128(18_446_744_073_709_551_557 * 18_446_744_073_709_551_533));
+    // Compare the Baillie-PSW test with the sieve for small numbers
+    let primes: Vec<u32> = simple_sieve(100_000);
+    let composites: usize = (41..=100_000u128)
+      .filter(|&x| x % 2 != 0 && primality::baillie_psw(x) != primes.binary_search(&(x as u32)).is_ok())
+      .count();
+    assert_eq!(composites, 0);
   }
 
   #[test]
@@ -301,6 +780,90 @@ mod tests {
     assert_eq!(split_into_factors(4_294_967_296), vec![2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
     assert_eq!(split_into_factors(18_446_744_073_709_551_614), vec![2, 7, 7, 73, 127, 337, 92_737, 649_657]);
     assert_eq!(split_into_factors(18_446_744_073_709_551_615), vec![3, 5, 17, 257, 641, 65_537, 6_700_417]);
+    // Semiprimes and powers of large primes
+    assert_eq!(split_into_factors(18_446_743_979_220_271_189), vec![4_294_967_279, 4_294_967_291]);
+    assert_eq!(split_into_factors(18_446_744_030_759_878_681), vec![4_294_967_291, 4_294_967_291]);
+    assert_eq!(split_into_factors(1_000_000_016_000_000_063), vec![1_000_000_007, 1_000_000_009]);
+    assert_eq!(split_into_factors(18_446_744_073_709_551_557), vec![18_446_744_073_709_551_557]);
+    assert_eq!(split_into_factors(8_589_934_582), vec![2, 4_294_967_291]);
+    assert_eq!(split_into_factors(2_147_483_647 * 2_147_483_647 * 3), vec![3, 2_147_483_647, 2_147_483_647]);
+    // Every factor is prime and their product is the number
+    for num in (18_446_744_073_709_000_000..18_446_744_073_709_000_500).chain(1_000_000..1_000_500) {
+      let factors: Vec<u64> = split_into_factors(num);
+      assert!(factors.windows(2).all(|pair| pair[0] <= pair[1]));
+      assert!(factors.iter().all(|&factor| is_prime(factor)));
+      assert_eq!(factors.iter().product::<u64>(), num);
+    }
+  }
+
+  #[test]
+  fn test_factorization() {
+    assert_eq!(factorize(0).factors(), &[]);
+    assert_eq!(factorize(1).factors(), &[]);
+    assert_eq!(factorize(4_294_967_296).factors(), &[(2, 32)]);
+    assert_eq!(factorize(4_294_967_292).factors(), &[(2, 2), (3, 2), (7, 1), (11, 1), (31, 1), (151, 1), (331, 1)]);
+    assert_eq!(factorize(1).to_string(), "1");
+    assert_eq!(factorize(7).to_string(), "7");
+    assert_eq!(factorize(4_294_967_296).to_string(), "2^32");
+    assert_eq!(factorize(5_544).to_string(), "2^3 * 3^2 * 7 * 11");
+    assert_eq!(factorize(1).value(), 1);
+    assert_eq!(factorize(5_544).value(), 5_544);
+    assert_eq!(factorize(18_446_744_073_709_551_615).value(), 18_446_744_073_709_551_615);
+    assert_eq!(Factorization::try_from(vec![2; 63]).unwrap().value(), 9_223_372_036_854_775_808);
+    assert!(matches!(Factorization::try_from(vec![2; 64]), Err(Error::InvalidFactors)));
+    assert!(matches!(Factorization::try_from(vec![0, 3]), Err(Error::InvalidFactors)));
+    assert!(matches!(Factorization::try_from(vec![1]), Err(Error::InvalidFactors)));
+    assert!(matches!(Factorization::try_from(vec![4, 3]), Err(Error::InvalidFactors)));
+    assert!(matches!(Factorization::try_from(vec![4_294_967_291, 4_294_967_311]), Err(Error::InvalidFactors)));
+    assert_eq!(Factorization::try_from(vec![7, 2, 3, 2]).unwrap(), factorize(84));
+    assert_eq!(Factorization::try_from(vec![]).unwrap(), factorize(1));
+    assert_eq!(Vec::<u64>::from(factorize(4_294_967_292)), split_into_factors(4_294_967_292));
+    assert_eq!(Vec::<u64>::from(factorize(1)), vec![]);
+  }
+
+  #[test]
+  fn test_arithmetic_functions() {
+    assert_eq!(divisors(0), vec![]);
+    assert_eq!(divisors(1), vec![1]);
+    assert_eq!(divisors(12), vec![1, 2, 3, 4, 6, 12]);
+    assert_eq!(divisors(97), vec![1, 97]);
+    assert_eq!(divisors(4_294_967_296).len(), 33);
+    assert_eq!(divisor_count(1), 1);
+    assert_eq!(divisor_count(12), 6);
+    assert_eq!(divisor_count(5_544), 48);
+    assert_eq!(divisor_count(897_612_484_786_617_600), 103_680);
+    assert_eq!(divisor_sum(1, 1), Some(1));
+    assert_eq!(divisor_sum(12, 0), Some(6));
+    assert_eq!(divisor_sum(12, 1), Some(28));
+    assert_eq!(divisor_sum(12, 2), Some(210));
+    assert_eq!(divisor_sum(18_446_744_073_709_551_557, 1), Some(18_446_744_073_709_551_558));
+    assert_eq!(divisor_sum(18_446_744_073_709_551_557, 3), None);
+    assert_eq!(totient(1), 1);
+    assert_eq!(totient(12), 4);
+    assert_eq!(totient(97), 96);
+    assert_eq!(totient(4_294_967_296), 2_147_483_648);
+    assert_eq!(totient(18_446_744_073_709_551_615), 9_208_981_628_670_443_520);
+    assert_eq!(mobius(1), 1);
+    assert_eq!(mobius(2), -1);
+    assert_eq!(mobius(6), 1);
+    assert_eq!(mobius(12), 0);
+    assert_eq!(mobius(30), -1);
+    assert_eq!(radical(1), 1);
+    assert_eq!(radical(12), 6);
+    assert_eq!(radical(4_294_967_296), 2);
+    assert_eq!(carmichael(1), 1);
+    assert_eq!(carmichael(2), 1);
+    assert_eq!(carmichael(4), 2);
+    assert_eq!(carmichael(8), 2);
+    assert_eq!(carmichael(16), 4);
+    assert_eq!(carmichael(15), 4);
+    assert_eq!(carmichael(561), 80);
+    assert_eq!(carmichael(5_544), 30);
+    for num in [0, 1, 2, 4, 7, 12, 97, 5_544, 65_536] {
+      assert_eq!(divisor_count(num), divisors(num).len() as u64);
+      assert_eq!(divisor_sum(num, 1), Some(divisors(num).iter().map(|&x| x as u128).sum()));
+      assert_eq!(totient(num), (1..=num).filter(|&x| gcd(x, num) == 1).count() as u64);
+    }
   }
 
   #[test]
@@ -382,6 +945,10 @@ mod tests {
     ]);
     assert_eq!(simple_sieve(10_000).len(), 1229);
     assert_eq!(simple_sieve(100_000).len(), 9592);
+    // Every position of the last byte of the wheel
+    for size in 0..300 {
+      assert_eq!(simple_sieve(size), (0..=size).filter(|&x| is_prime(x as u64)).collect::<Vec<u32>>());
+    }
     assert_eq!(simple_sieve(1_000_000).len(), 78498);
     assert_eq!(simple_sieve(10_000_000).len(), 664579);
   }
@@ -405,8 +972,729 @@ mod tests {
     assert_eq!(count_primes(2, None, None, None), 1);
     assert_eq!(count_primes(1, None, None, None), 0);
     assert_eq!(count_primes(0, None, None, None), 0);
+    assert_eq!(count_primes(3, None, None, None), 2);
+    assert_eq!(count_primes(53, Some(5), Some(1), Some(2)), 14);
+    // Windows at the top of the range
+    assert_eq!(count_primes(18_446_744_073_709_551_615, Some(18_446_744_073_709_551_615), None, None), 0);
+    assert_eq!(count_primes(18_446_744_073_709_551_557, Some(18_446_744_073_709_551_557), None, None), 1);
+    assert_eq!(count_primes(18_446_744_073_709_551_615, Some(18_446_744_073_709_551_515), Some(8), Some(1)), 3);
+    assert_eq!(count_primes(18_446_744_073_709_551_615, Some(18_446_744_073_709_541_615), None, None), 218);
+    assert_eq!(count_primes(18_446_744_073_709_451_615, Some(18_446_744_073_709_351_615), Some(3), Some(64)), 2265);
+    assert_eq!(count_primes(18_446_744_073_709_551_615, Some(18_446_744_073_708_551_615), Some(4), None), 22475);
+    assert_eq!(count_primes(18_446_744_073_001_000_000, Some(18_446_744_073_000_000_000), None, None), 22584);
+    // Compare small segments with the simple sieve
+    let primes: Vec<u32> = simple_sieve(300);
+    for start in (0..100).step_by(7) {
+      for limit in (start..300).step_by(5) {
+        let expected: usize = primes.iter().filter(|&&x| x as usize >= start && x as usize <= limit).count();
+        assert_eq!(count_primes(limit, Some(start), Some(1), Some(2)), expected);
+      }
+    }
+    // Small segments around the end of the presieve pattern (7 * 11 * 13 * 17 * 30)
+    assert_eq!(count_primes(520_000, Some(500_000), Some(1), Some(1)), (500_000..=520_000).filter(|&x| is_prime(x)).count());
+    // Segments much smaller than the square root, so most primes go to the buckets
+    assert_eq!(count_primes(1_000_000_100_000, Some(1_000_000_000_000), Some(2), Some(64)), (1_000_000_000_000..=1_000_000_100_000).filter(|&x| is_prime(x)).count());
     // Test with explicit threads and cache
     assert_eq!(count_primes(100, None, Some(1), Some(1)), 25);
     assert_eq!(count_primes(100, None, Some(4), Some(2)), 25);
   }
+
+  #[test]
+  fn test_primes_in_range() {
+    assert_eq!(primes_in_range(0, 0).collect::<Vec<usize>>(), vec![]);
+    assert_eq!(primes_in_range(0, 2).collect::<Vec<usize>>(), vec![2]);
+    assert_eq!(primes_in_range(0, 3).collect::<Vec<usize>>(), vec![2, 3]);
+    assert_eq!(primes_in_range(0, 30).collect::<Vec<usize>>(), vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+    assert_eq!(primes_in_range(4, 30).collect::<Vec<usize>>(), vec![5, 7, 11, 13, 17, 19, 23, 29]);
+    assert_eq!(primes_in_range(24, 28).collect::<Vec<usize>>(), vec![]);
+    assert_eq!(primes_in_range(30, 10).collect::<Vec<usize>>(), vec![]);
+    assert_eq!(primes_in_range(97, 101).collect::<Vec<usize>>(), vec![97, 101]);
+    assert_eq!(primes_in_range(4_294_967_280, 4_294_967_311).collect::<Vec<usize>>(), vec![4_294_967_291, 4_294_967_311]);
+    assert_eq!(primes_in_range(0, 10_000_000).count(), 664579);
+    assert_eq!(primes_in_range(1_000_000_000, 1_010_000_000).count(), count_primes(1_010_000_000, Some(1_000_000_000), None, None));
+    // Narrow ranges near the top are sieved with the small primes and tested
+    assert_eq!(primes_in_range(18_446_744_073_709_551_500, 18_446_744_073_709_551_615).collect::<Vec<usize>>(), vec![18_446_744_073_709_551_521, 18_446_744_073_709_551_533, 18_446_744_073_709_551_557]);
+    assert!(primes_in_range(1_000_000_000_000, 1_000_000_100_000).eq((1_000_000_000_000..=1_000_000_100_000).filter(|&x| is_prime(x as u64))));
+    let primes: Vec<u32> = simple_sieve(1_000_000);
+    assert!(primes_in_range(0, 1_000_000).zip(primes.iter()).all(|(x, &y)| x == y as usize));
+  }
+
+  #[test]
+  fn test_count_primes_lmo() {
+    assert_eq!(count_primes_lmo(0), 0);
+    assert_eq!(count_primes_lmo(1), 0);
+    assert_eq!(count_primes_lmo(2), 1);
+    assert_eq!(count_primes_lmo(100), 25);
+    assert_eq!(count_primes_lmo(65_535), 6542);
+    assert_eq!(count_primes_lmo(65_536), 6542);
+    assert_eq!(count_primes_lmo(100_000), 9592);
+    assert_eq!(count_primes_lmo(1_000_000), 78498);
+    assert_eq!(count_primes_lmo(10_000_000), 664579);
+    assert_eq!(count_primes_lmo(100_000_000), 5761455);
+    assert_eq!(count_primes_lmo(1_000_000_000), 50847534);
+    assert_eq!(count_primes_lmo(4_294_967_296), 203280221);
+    for limit in (1_000_000..1_001_000).step_by(97).chain([999_983, 999_984, 1_018_081, 1_018_080]) {
+      assert_eq!(count_primes_lmo(limit), count_primes(limit, None, None, None));
+    }
+  }
+
+  #[test]
+  fn test_nth_prime() {
+    assert_eq!(nth_prime(0), None);
+    assert_eq!(nth_prime(1), Some(2));
+    assert_eq!(nth_prime(2), Some(3));
+    assert_eq!(nth_prime(3), Some(5));
+    assert_eq!(nth_prime(10), Some(29));
+    assert_eq!(nth_prime(1_000), Some(7919));
+    assert_eq!(nth_prime(65_535), Some(821_603));
+    assert_eq!(nth_prime(65_536), Some(821_641));
+    assert_eq!(nth_prime(1_000_000), Some(15_485_863));
+    assert_eq!(nth_prime(10_000_000), Some(179_424_673));
+    assert_eq!(nth_prime(100_000_000), Some(2_038_074_743));
+    assert_eq!(nth_prime(203_280_221), Some(4_294_967_291));
+    assert_eq!(prime_pi(4_294_967_291), 203_280_221);
+    assert_eq!(prime_pi(4_294_967_290), 203_280_220);
+    for n in (1_000_000..1_000_100).step_by(9) {
+      let prime: usize = nth_prime(n).unwrap();
+      assert!(is_prime(prime as u64));
+      assert_eq!(prime_pi(prime), n);
+    }
+  }
+
+  #[test]
+  fn test_next_prev_prime() {
+    assert_eq!(next_prime(0), Some(2));
+    assert_eq!(next_prime(1), Some(2));
+    assert_eq!(next_prime(2), Some(3));
+    assert_eq!(next_prime(3), Some(5));
+    assert_eq!(next_prime(13), Some(17));
+    assert_eq!(next_prime(1_000_000), Some(1_000_003));
+    assert_eq!(next_prime(4_294_967_291), Some(4_294_967_311));
+    assert_eq!(next_prime(18_446_744_073_709_551_556), Some(18_446_744_073_709_551_557));
+    assert_eq!(next_prime(18_446_744_073_709_551_557), None);
+    assert_eq!(next_prime(18_446_744_073_709_551_615), None);
+    assert_eq!(prev_prime(0), None);
+    assert_eq!(prev_prime(2), None);
+    assert_eq!(prev_prime(3), Some(2));
+    assert_eq!(prev_prime(4), Some(3));
+    assert_eq!(prev_prime(17), Some(13));
+    assert_eq!(prev_prime(1_000_003), Some(999_983));
+    assert_eq!(prev_prime(4_294_967_311), Some(4_294_967_291));
+    assert_eq!(prev_prime(18_446_744_073_709_551_615), Some(18_446_744_073_709_551_557));
+    assert_eq!(next_prime_u128(18_446_744_073_709_551_557), Some(18_446_744_073_709_551_629));
+    assert_eq!(next_prime_u128(0), Some(2));
+    assert_eq!(next_prime_u128(u128::MAX - 159), Some(u128::MAX - 158));
+    assert_eq!(next_prime_u128(u128::MAX - 158), None);
+    assert_eq!(next_prime_u128(u128::MAX), None);
+    assert_eq!(prev_prime_u128(u128::MAX), Some(u128::MAX - 158));
+    assert_eq!(prev_prime_u128(18_446_744_073_709_551_629), Some(18_446_744_073_709_551_557));
+    assert_eq!(prev_prime_u128(2), None);
+    // Compare with the sieve for small numbers
+    let primes: Vec<u32> = simple_sieve(10_000);
+    for window in primes.windows(2) {
+      assert_eq!(next_prime(window[0] as u64), Some(window[1] as u64));
+      assert_eq!(prev_prime(window[1] as u64), Some(window[0] as u64));
+      assert_eq!(next_prime(window[1] as u64 - 1), Some(window[1] as u64));
+      assert_eq!(prev_prime(window[0] as u64 + 1), Some(window[0] as u64));
+    }
+  }
+
+  #[test]
+  fn test_bit_sieve() {
+    // Compare every small range with is_prime
+    for low in 0..40_usize {
+      for high in low.saturating_sub(1)..100 {
+        let sieve: BitSieve = BitSieve::new(low, high);
+        let expected: Vec<usize> = (low..=high).filter(|&x| is_prime(x as u64)).collect();
+        assert_eq!(sieve.iter().collect::<Vec<usize>>(), expected);
+        assert_eq!(sieve.count(), expected.len());
+        for x in 0..=100 {
+          assert_eq!(sieve.get(x), if x < low || x > high { None } else { Some(expected.contains(&x)) });
+          assert_eq!(sieve.rank(x), expected.iter().filter(|&&p| p <= x).count());
+        }
+        for k in 0..=expected.len() + 1 {
+          assert_eq!(sieve.select(k), if k == 0 { None } else { expected.get(k - 1).copied() });
+        }
+      }
+    }
+    // A larger range against the segmented sieve
+    let sieve: BitSieve = BitSieve::new(1_000_000, 3_000_000);
+    assert!(sieve.iter().eq(primes_in_range(1_000_000, 3_000_000)));
+    assert_eq!(sieve.rank(2_000_000), count_primes(2_000_000, Some(1_000_000), None, None));
+    assert_eq!(sieve.select(1), Some(1_000_003));
+    assert_eq!(sieve.select(sieve.count()), Some(2_999_999));
+    // Set and clear
+    let mut sieve: BitSieve = BitSieve::new(0, 100);
+    assert_eq!(sieve.set(4), None);
+    assert_eq!(sieve.set(101), None);
+    assert_eq!(sieve.clear(4), Some(false));
+    assert_eq!(sieve.clear(2), Some(true));
+    assert_eq!(sieve.set(2), Some(false));
+    assert_eq!(sieve.clear(97), Some(true));
+    assert_eq!(sieve.get(97), Some(false));
+    assert_eq!(sieve.set(91), Some(false));
+    assert_eq!(sieve.get(91), Some(true));
+    assert_eq!(sieve.rank(100), 25);
+    // Serialization
+    let bytes: Vec<u8> = sieve.to_bytes();
+    assert_eq!(BitSieve::from_bytes(&bytes), Some(sieve.clone()));
+    assert_eq!(BitSieve::from_bytes(&bytes[..bytes.len() - 1]), None);
+    assert_eq!(BitSieve::from_bytes(&[]), None);
+    // A header with a huge range and few words is rejected without allocating the bitmap
+    let mut forged: Vec<u8> = Vec::new();
+    forged.extend_from_slice(&0u64.to_le_bytes());
+    forged.extend_from_slice(&u64::MAX.to_le_bytes());
+    forged.push(0);
+    forged.extend_from_slice(&0u64.to_le_bytes());
+    assert_eq!(BitSieve::from_bytes(&forged), None);
+    let sieve: BitSieve = BitSieve::new(10, 5);
+    assert_eq!(sieve.count(), 0);
+    assert_eq!(BitSieve::from_bytes(&sieve.to_bytes()), Some(sieve));
+    // Ranges
+    let sieve: BitSieve = BitSieve::new(0, 1000);
+    for (start, end) in [(0, 1000), (0, 1), (2, 5), (4, 100), (7, 7), (100, 90), (31, 59), (500, 2000)] {
+      let expected: Vec<usize> = sieve.iter().filter(|&prime| start <= prime && prime <= end).collect();
+      assert_eq!(sieve.range(start, end).collect::<Vec<usize>>(), expected);
+    }
+  }
+
+  #[test]
+  fn test_prime_table() {
+    let path: std::path::PathBuf = std::env::temp_dir().join(format!("primeutils-test-{}.tbl", std::process::id()));
+
+    PrimeTable::build(&path, 1_000, 200_000).unwrap();
+    let table: PrimeTable = PrimeTable::open(&path).unwrap();
+    assert_eq!((table.low(), table.high()), (1_000, 200_000));
+    assert_eq!(table.sieve(), &BitSieve::new(1_000, 200_000));
+    assert_eq!(table.is_prime(199_999), Some(true));
+    assert_eq!(table.is_prime(199_997), Some(false));
+    assert_eq!(table.is_prime(999), None);
+    assert_eq!(table.count_primes(1_000, 200_000), Some(count_primes(200_000, Some(1_000), None, None)));
+    assert_eq!(table.count_primes(50_000, 60_000), Some(count_primes(60_000, Some(50_000), None, None)));
+    assert_eq!(table.count_primes(0, 60_000), None);
+    assert_eq!(table.primes(1_000, 1_100).unwrap().collect::<Vec<usize>>(), primes_in_range(1_000, 1_100).collect::<Vec<usize>>());
+    assert!(table.primes(1_000, 200_001).is_none());
+    drop(table);
+
+    // A table with the wheel primes
+    PrimeTable::build(&path, 0, 100).unwrap();
+    let table: PrimeTable = PrimeTable::open(&path).unwrap();
+    assert_eq!(table.count_primes(0, 100), Some(25));
+    assert_eq!(table.primes(3, 13).unwrap().collect::<Vec<usize>>(), vec![3, 5, 7, 11, 13]);
+    drop(table);
+
+    // Corrupted files are only rejected by verify, which reads the whole payload
+    let mut bytes: Vec<u8> = std::fs::read(&path).unwrap();
+    assert!(PrimeTable::open(&path).unwrap().verify().is_ok());
+    let last: usize = bytes.len() - 1;
+    bytes[last] ^= 1;
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(PrimeTable::open(&path).unwrap().verify(), Err(Error::InvalidData(message)) if message == "prime table checksum mismatch"));
+    // Sizes that don't match the file are rejected when opening, even with a valid checksum
+    let mut forged: Vec<u8> = bytes[..24].to_vec();
+    let mut payload: Vec<u8> = Vec::new();
+    payload.extend_from_slice(&0u64.to_le_bytes());
+    payload.extend_from_slice(&u64::MAX.to_le_bytes());
+    payload.push(0);
+    payload.extend_from_slice(&0u64.to_le_bytes());
+    let hash: u64 = payload.iter().fold(0xcbf29ce484222325, |hash: u64, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    forged[16..24].copy_from_slice(&hash.to_le_bytes());
+    forged.extend_from_slice(&payload);
+    std::fs::write(&path, &forged).unwrap();
+    assert!(matches!(PrimeTable::open(&path), Err(Error::InvalidData(_))));
+    bytes[0] = b'X';
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(PrimeTable::open(&path), Err(Error::InvalidData(_))));
+    std::fs::write(&path, b"PRIME").unwrap();
+    assert!(matches!(PrimeTable::open(&path), Err(Error::InvalidData(_))));
+
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_count_primes_resume() {
+    let path: std::path::PathBuf = std::env::temp_dir().join(format!("primeutils-test-{}.ckpt", std::process::id()));
+    let (start, limit, cache): (usize, usize, usize) = (1_000, 10_000_000, 1 << 10);
+    let expected: usize = count_primes(limit, Some(start), Some(2), Some(cache));
+    let options = |threads: usize| CountOptions { threads: Some(threads), cache: Some(cache), ..Default::default() };
+
+    // From scratch, and again from the finished checkpoint
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(count_primes_resume(limit, Some(start), options(2), &path).unwrap().primes, expected);
+    assert_eq!(count_primes_resume(limit, Some(start), CountOptions::default(), &path).unwrap().primes, expected);
+
+    // From a checkpoint with some chunks done out of order
+    let mut checkpoint: checkpoint::Checkpoint = checkpoint::Checkpoint::new(start, limit, chunk_size(start, limit, 2, cache));
+    for chunk in [5, 0, 2, 3, 1, checkpoint.chunks() - 1] {
+      let low: usize = start + chunk * checkpoint.chunk_size;
+      let high: usize = std::cmp::min(low + checkpoint.chunk_size - 1, limit);
+      // The sieving primes are counted apart, and not in the chunks
+      let sieving: usize = simple_sieve(limit.isqrt() as u32).iter().filter(|&&prime| low <= prime as usize && prime as usize <= high).count();
+      checkpoint.add(chunk, count_primes(high, Some(low), Some(1), None) - sieving);
+    }
+    checkpoint.save(&path).unwrap();
+    assert_eq!(checkpoint::Checkpoint::load(&path).unwrap(), checkpoint);
+    assert_eq!(count_primes_resume(limit, Some(start), options(2), &path).unwrap().primes, expected);
+
+    // Narrow ranges near the top are split in chunks for every thread too
+    for width in [100_000_000_000, 1_000_000_000, 100_000_000] {
+      let low: usize = usize::MAX - width;
+      assert!(checkpoint::Checkpoint::new(low, usize::MAX, chunk_size(low, usize::MAX, 8, 1 << 15)).chunks() >= 8);
+    }
+
+    // Chunks marked done are not sieved again
+    let mut checkpoint: checkpoint::Checkpoint = checkpoint::Checkpoint::new(start, limit, chunk_size(start, limit, 2, cache));
+    checkpoint.add(1, 0);
+    checkpoint.save(&path).unwrap();
+    assert!(count_primes_resume(limit, Some(start), options(2), &path).unwrap().primes < expected);
+
+    // A cancelled count keeps its chunks in the checkpoint
+    let cancel: CancelToken = CancelToken::new();
+    let canceller: CancelToken = cancel.clone();
+    let _ = std::fs::remove_file(&path);
+    let partial: CountResult = count_primes_resume(limit, Some(start), CountOptions {
+      progress: Some(Box::new(move |progress: &Progress| if progress.done >= 10 { canceller.cancel() })),
+      cancel: Some(cancel),
+      ..options(2)
+    }, &path).unwrap();
+    assert!(partial.cancelled && partial.end >= start);
+    assert_eq!(partial.primes, count_primes(partial.end, Some(start), None, None));
+    assert_eq!(count_primes_resume(limit, Some(start), options(2), &path).unwrap().primes, expected);
+
+    // Checkpoints of another count or invalid are rejected
+    let invalid = |result: Result<CountResult>| matches!(result, Err(Error::InvalidData(_)));
+    assert!(matches!(count_primes_resume(limit + 1, Some(start), CountOptions::default(), &path), Err(Error::CheckpointMismatch { start: 1_000, limit: 10_000_000 })));
+    std::fs::write(&path, "primeutils count checkpoint 1\nstart 1000\nlimit 10000000\nchunk 0\ndone\n").unwrap();
+    assert!(invalid(count_primes_resume(limit, Some(start), CountOptions::default(), &path)));
+    std::fs::write(&path, "primeutils count checkpoint 1\nstart 1000\nlimit 10000000\nchunk 30\ndone 3-4:1 5-5:0\n").unwrap();
+    assert!(invalid(count_primes_resume(limit, Some(start), CountOptions::default(), &path)));
+
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_count_primes_with() {
+    let (start, limit): (usize, usize) = (100, 20_000_000);
+    let expected: usize = count_primes(limit, Some(start), None, None);
+
+    // The progress is reported for every chunk, and the primes of the last one are all but the sieving ones
+    let reports: std::rc::Rc<std::cell::RefCell<Vec<Progress>>> = Default::default();
+    let recorder: std::rc::Rc<std::cell::RefCell<Vec<Progress>>> = reports.clone();
+    let result: CountResult = count_primes_with(limit, Some(start), CountOptions {
+      threads: Some(3),
+      cache: Some(1 << 12),
+      progress: Some(Box::new(move |progress: &Progress| recorder.borrow_mut().push(*progress))),
+      cancel: None,
+    }).unwrap();
+    assert_eq!(result, CountResult { primes: expected, start, end: limit, cancelled: false });
+    let reports: Vec<Progress> = reports.take();
+    assert!(reports.windows(2).all(|pair| pair[0].done + 1 == pair[1].done && pair[0].primes <= pair[1].primes));
+    let last: Progress = reports[reports.len() - 1];
+    assert_eq!((last.done, last.total, reports.len()), (last.total, last.total, last.total));
+    assert_eq!(last.primes, expected - simple_sieve(limit.isqrt() as u32).iter().filter(|&&prime| prime as usize >= start).count());
+
+    // Cancelled after some chunks, the result covers the chunks from the start without gaps
+    let cancel: CancelToken = CancelToken::new();
+    let canceller: CancelToken = cancel.clone();
+    let result: CountResult = count_primes_with(limit, Some(start), CountOptions {
+      threads: Some(4),
+      cache: Some(1 << 12),
+      progress: Some(Box::new(move |progress: &Progress| if progress.done >= 5 { canceller.cancel() })),
+      cancel: Some(cancel),
+    }).unwrap();
+    assert!(result.cancelled && result.end < limit);
+    assert_eq!(result.primes, if result.end < start { 0 } else { count_primes(result.end, Some(start), None, None) });
+
+    // Cancelled before starting
+    let cancel: CancelToken = CancelToken::new();
+    cancel.cancel();
+    let result: CountResult = count_primes_with(limit, Some(start), CountOptions { cancel: Some(cancel), ..Default::default() }).unwrap();
+    assert_eq!(result, CountResult { primes: 0, start, end: start - 1, cancelled: true });
+  }
+
+  #[test]
+  fn test_shards() {
+    // The shards cover the range exactly once
+    for (start, limit, count) in [(2, 100, 7), (0, usize::MAX, 3), (5, 5, 1), (1_000, 1_010, 11)] {
+      let mut next: usize = start;
+      for index in 1..=count {
+        let (low, high): (usize, usize) = Shard::new(index, count).unwrap().range(start, limit).unwrap();
+        assert!(low == next && low <= high);
+        next = high.wrapping_add(1);
+      }
+      assert_eq!(next, limit.wrapping_add(1));
+    }
+    assert_eq!(Shard::new(0, 3), None);
+    assert_eq!(Shard::new(4, 3), None);
+    assert_eq!(Shard::new(1, 12).unwrap().range(1_000, 1_010), None);
+
+    // The partial counts add up to the whole count
+    let (start, limit): (usize, usize) = (1_000, 30_000_000);
+    let options = || CountOptions { threads: Some(2), cache: Some(1 << 12), ..Default::default() };
+    let parts: Vec<PartialCount> = (1..=4)
+      .map(|index| count_primes_shard(limit, Some(start), Shard::new(index, 4).unwrap(), options(), None).unwrap())
+      .collect();
+    let expected: CountResult = CountResult { primes: count_primes(limit, Some(start), None, None), start, end: limit, cancelled: false };
+    assert_eq!(merge_counts(&parts).unwrap(), expected);
+    assert_eq!(merge_counts(&[parts[3], parts[1], parts[0], parts[2]]).unwrap(), expected);
+    for part in &parts {
+      assert_eq!(PartialCount::from_text(&part.to_text()).unwrap(), *part);
+    }
+
+    // Missing, repeated, overlapping or foreign parts are rejected
+    let mismatch = |parts: &[PartialCount]| matches!(merge_counts(parts), Err(Error::ShardMismatch(_)));
+    assert!(mismatch(&[]));
+    assert!(mismatch(&parts[..3]));
+    assert!(mismatch(&[parts[0], parts[1], parts[2], parts[3], parts[3]]));
+    assert!(mismatch(&[parts[0], parts[1], parts[2], PartialCount { low: parts[3].low - 1, ..parts[3] }]));
+    assert!(mismatch(&[parts[0], parts[1], PartialCount { high: parts[2].high - 1, ..parts[2] }, parts[3]]));
+    assert!(mismatch(&[parts[0], parts[1], parts[2], PartialCount { limit: limit + 1, ..parts[3] }]));
+    assert!(mismatch(&[parts[0], PartialCount { high: parts[1].high + 5, ..parts[1] }, PartialCount { low: parts[2].low + 5, ..parts[2] }, parts[3]]));
+    assert!(mismatch(&[parts[0], parts[1], parts[2], PartialCount { segment_size: parts[3].segment_size * 2, ..parts[3] }]));
+    assert_eq!(merge_counts(&parts[1..]).unwrap_err().to_string(), "shard 1/4 is missing");
+    assert!(matches!(PartialCount::from_text(&parts[0].to_text().replace("range 1000..", "range 999..")), Err(Error::InvalidData(_))));
+    assert!(PartialCount::from_text("primeutils partial count 2\n").is_err());
+
+    // The shards can be resumed from checkpoints too
+    let path: std::path::PathBuf = std::env::temp_dir().join(format!("primeutils-test-shard-{}.ckpt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(count_primes_shard(limit, Some(start), Shard::new(2, 4).unwrap(), options(), Some(&path)).unwrap(), parts[1]);
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_coordinator() {
+    use std::io::{BufRead, Write};
+
+    let (start, limit): (usize, usize) = (1_000, 20_000_000);
+    let listener: std::net::TcpListener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address: std::net::SocketAddr = listener.local_addr().unwrap();
+    let coordinator = std::thread::spawn(move || serve_coordinator(listener, limit, Some(start), CoordinatorOptions {
+      work_size: Some(1_000_000),
+      timeout: Some(std::time::Duration::from_millis(500)),
+      progress: None,
+    }));
+
+    // A worker that takes a range and never returns it, so it has to be given to another one
+    let stalled: std::net::TcpStream = std::net::TcpStream::connect(address).unwrap();
+    let mut reader: std::io::BufReader<std::net::TcpStream> = std::io::BufReader::new(stalled.try_clone().unwrap());
+    let mut line: String = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "PRIMEUTILS 1\n");
+    writeln!(&stalled, "NEXT").unwrap();
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    assert!(line.starts_with("WORK 0 1000 "));
+
+    // Invalid lines close the connection
+    let invalid: std::net::TcpStream = std::net::TcpStream::connect(address).unwrap();
+    let invalid_reader: std::io::BufReader<std::net::TcpStream> = std::io::BufReader::new(invalid.try_clone().unwrap());
+    writeln!(&invalid, "RESULT 100000 5").unwrap();
+    let lines: Vec<String> = invalid_reader.lines().map(|line| line.unwrap()).collect();
+    assert_eq!(lines, vec!["PRIMEUTILS 1", "ERROR invalid result"]);
+
+    // And so do results of ranges never given, or given to another worker
+    for id in [0, 5] {
+      let foreign: std::net::TcpStream = std::net::TcpStream::connect(address).unwrap();
+      let foreign_reader: std::io::BufReader<std::net::TcpStream> = std::io::BufReader::new(foreign.try_clone().unwrap());
+      writeln!(&foreign, "RESULT {id} 5").unwrap();
+      let lines: Vec<String> = foreign_reader.lines().map(|line| line.unwrap()).collect();
+      assert_eq!(lines, vec![String::from("PRIMEUTILS 1"), format!("ERROR range {id} was not given to this worker")]);
+    }
+
+    let workers: Vec<std::thread::JoinHandle<usize>> = (0..2)
+      .map(|_| std::thread::spawn(move || run_worker(address, Some(1), Some(1 << 12)).unwrap()))
+      .collect();
+    let ranges: usize = workers.into_iter().map(|worker| worker.join().unwrap()).sum();
+
+    let result: CountResult = coordinator.join().unwrap().unwrap();
+    assert_eq!(result, CountResult { primes: count_primes(limit, Some(start), None, None), start, end: limit, cancelled: false });
+    // A slow worker may time out too, and then its range is counted twice
+    assert!(ranges >= 20);
+    drop(stalled);
+  }
+
+  #[test]
+  fn test_cache_info() {
+    assert_eq!(cpu::parse_size("48K\n"), Some(48 << 10));
+    assert_eq!(cpu::parse_size("30M"), Some(30 << 20));
+    assert_eq!(cpu::parse_size("512"), Some(512));
+    assert_eq!(cpu::parse_size("K"), None);
+    assert_eq!(cpu::parse_size(""), None);
+    assert_eq!(cpu::parse_cpu_list("0\n"), Some(1));
+    assert_eq!(cpu::parse_cpu_list("0-3,8-11"), Some(8));
+    assert_eq!(cpu::parse_cpu_list("0,2,4"), Some(3));
+    assert_eq!(cpu::parse_cpu_list("3-1"), None);
+
+    // A directory like the one of sysfs, with an instruction cache that must be skipped
+    let dir: std::path::PathBuf = std::env::temp_dir().join(format!("primeutils-test-cache-{}", std::process::id()));
+    let caches: [(&str, &str, &str, &str); 4] = [
+      ("1", "Instruction", "64K", "0-1"),
+      ("1", "Data", "32K", "0-1"),
+      ("2", "Unified", "1024K", "0-3"),
+      ("3", "Unified", "16M", "0-15"),
+    ];
+    for (i, (level, kind, size, shared)) in caches.iter().enumerate() {
+      let index: std::path::PathBuf = dir.join(format!("index{i}"));
+      std::fs::create_dir_all(&index).unwrap();
+      for (name, value) in [("level", level), ("type", kind), ("size", size), ("shared_cpu_list", shared)] {
+        std::fs::write(index.join(name), format!("{value}\n")).unwrap();
+      }
+    }
+    let info: Option<cpu::CacheInfo> = cpu::read_sysfs_cache(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let info: cpu::CacheInfo = info.unwrap();
+    assert_eq!(info.l1, Some(cpu::Cache { size: 32 << 10, shared_by: 2 }));
+    assert_eq!(info.l2, Some(cpu::Cache { size: 1 << 20, shared_by: 4 }));
+    assert_eq!(info.l3, Some(cpu::Cache { size: 16 << 20, shared_by: 16 }));
+    assert_eq!((info.l1_size(), info.l2_size()), (16 << 10, 256 << 10));
+    assert_eq!(cpu::read_sysfs_cache(&dir), None);
+
+    // Without L2 the segments are sized for L1, and without L1 for the default size
+    assert_eq!(cpu::CacheInfo { l2: None, ..info }.l2_size(), 16 << 10);
+    assert_eq!(cpu::CacheInfo::default().l1_size(), 32768);
+
+    // Any block and segment sizes give the same count
+    let (low, high): (usize, usize) = (1_000_000, 3_000_000);
+    let primes: Vec<u32> = simple_sieve(high.isqrt() as u32);
+    let expected: usize = count_primes(high, Some(low), Some(1), Some(1 << 15));
+    for (block, segment) in [(1, 1), (8, 1 << 12), (1 << 10, 1 << 15), (1 << 15, 1 << 10)] {
+      let mut sieve: bucket::BucketSieve<u64> = bucket::BucketSieve::new(&primes, low, high, block, segment);
+      let mut count: usize = 0;
+      while let Some((base, segment)) = sieve.next_segment() {
+        count += count_sieve(segment, base, u64::MAX);
+      }
+      assert_eq!(count, expected);
+    }
+
+    // A reset sieve counts another range like a new one, even a lower one
+    let mut sieve: bucket::BucketSieve<u64> = bucket::BucketSieve::new(&primes, 1, 0, 1 << 10, 1 << 12);
+    for (low, high) in [(2_000_000, 3_000_000), (10, 1_000_000), (2_999_990, 3_000_000), (1_000_000, 1_000_000)] {
+      sieve.reset(&primes, low, high);
+      let mut count: usize = 0;
+      while let Some((base, segment)) = sieve.next_segment() {
+        count += count_sieve(segment, base, u64::MAX);
+      }
+      let sieving: usize = primes.iter().filter(|&&prime| low <= prime as usize && prime as usize <= high && prime >= 7).count();
+      assert_eq!(count + sieving, (low..=high).filter(|&x| x >= 7 && is_prime(x as u64)).count());
+    }
+  }
+
+  #[test]
+  fn test_thread_count() {
+    use cpu::{ThreadCount, ThreadSource};
+
+    let count = |threads: usize, source: ThreadSource| ThreadCount { threads, source };
+    assert_eq!(cpu::choose_threads(Some("3"), Some(2), Some(8), 16), count(3, ThreadSource::Env));
+    assert_eq!(cpu::choose_threads(Some("0"), Some(2), Some(8), 16), count(2, ThreadSource::Quota));
+    assert_eq!(cpu::choose_threads(Some("many"), None, Some(8), 16), count(8, ThreadSource::Affinity));
+    assert_eq!(cpu::choose_threads(None, Some(8), Some(8), 16), count(8, ThreadSource::Quota));
+    assert_eq!(cpu::choose_threads(None, Some(12), Some(8), 16), count(8, ThreadSource::Affinity));
+    assert_eq!(cpu::choose_threads(None, Some(4), None, 16), count(4, ThreadSource::Quota));
+    assert_eq!(cpu::choose_threads(None, None, None, 16), count(16, ThreadSource::Fallback));
+    assert_eq!(cpu::choose_threads(None, None, None, 0), count(1, ThreadSource::Fallback));
+
+    assert_eq!(cpu::parse_cpu_max("150000 100000\n"), Some(2));
+    assert_eq!(cpu::parse_cpu_max("200000 100000"), Some(2));
+    assert_eq!(cpu::parse_cpu_max("max 100000"), None);
+
+    // cgroup v2, with a lower quota in the parent, and cgroup v1 with the cpu controller mounted with cpuacct
+    let root: std::path::PathBuf = std::env::temp_dir().join(format!("primeutils-test-cgroup-{}", std::process::id()));
+    let write = |path: &str, text: &str| {
+      let path: std::path::PathBuf = root.join(path);
+      std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+      std::fs::write(path, text).unwrap();
+    };
+    write("cpu.max", "max 100000\n");
+    write("jobs/cpu.max", "300000 100000\n");
+    write("jobs/count/cpu.max", "max 100000\n");
+    write("cpu,cpuacct/docker/cpu.cfs_quota_us", "-1\n");
+    write("cpu,cpuacct/docker/cpu.cfs_period_us", "100000\n");
+    write("cpu,cpuacct/docker/a/cpu.cfs_quota_us", "50000\n");
+    write("cpu,cpuacct/docker/a/cpu.cfs_period_us", "100000\n");
+
+    let v2: Option<usize> = cpu::read_cgroup_quota(&root, "0::/jobs/count\n");
+    let v1: Option<usize> = cpu::read_cgroup_quota(&root, "5:memory:/docker/a\n4:cpu,cpuacct:/docker/a\n");
+    let unlimited: Option<usize> = cpu::read_cgroup_quota(&root, "0::/\n4:cpu,cpuacct:/docker\n");
+    let missing: Option<usize> = cpu::read_cgroup_quota(&root, "0::/other\n");
+    std::fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(v2, Some(3));
+    assert_eq!(v1, Some(1));
+    assert_eq!(unlimited, None);
+    assert_eq!(missing, None);
+  }
+
+  #[test]
+  fn test_tune() {
+    let path: std::path::PathBuf = std::env::temp_dir().join(format!("primeutils-test-{}.profile", std::process::id()));
+    let profile: Profile = Profile { machine: machine_name(), threads: 3, cache: 1 << 16 };
+    profile.save(&path).unwrap();
+    assert_eq!(Profile::load(&path).unwrap(), profile);
+
+    // Profiles with missing or zero values are rejected
+    std::fs::write(&path, "primeutils tune profile 1\nmachine a\nthreads 0\ncache 4096\n").unwrap();
+    assert!(matches!(Profile::load(&path), Err(Error::InvalidData(_))));
+    std::fs::write(&path, "primeutils tune profile 1\nmachine a\nthreads 2\n").unwrap();
+    assert!(matches!(Profile::load(&path), Err(Error::InvalidData(_))));
+    std::fs::remove_file(&path).unwrap();
+
+    // Every combination of the grid is tried, and the fastest one is chosen
+    let result: TuneResult = tune(TuneOptions {
+      limit: Some(1_000_000_000),
+      trial_time: Some(Duration::from_millis(5)),
+      caches: Some(vec![1 << 12, 1 << 14]),
+      threads: Some(vec![1, 2]),
+    }).unwrap();
+    let combinations: Vec<(usize, usize)> = result.trials.iter().map(|trial| (trial.threads, trial.cache)).collect();
+    assert_eq!(combinations, vec![(1, 1 << 12), (1, 1 << 14), (2, 1 << 12), (2, 1 << 14)]);
+    let fastest: &Trial = result.trials.iter().min_by_key(|trial| trial.time).unwrap();
+    assert_eq!((result.profile.threads, result.profile.cache), (fastest.threads, fastest.cache));
+    assert_eq!(result.profile.machine, machine_name());
+    assert!(result.low == 1_000_000_000 && result.high >= result.low);
+  }
+
+  #[test]
+  fn test_sieve_config() {
+    assert!(matches!(SieveConfig::new(100).threads(0).count(), Err(Error::Config(ConfigError::ZeroThreads))));
+    assert!(matches!(SieveConfig::new(100).segment_bytes(0).count(), Err(Error::Config(ConfigError::ZeroSegmentBytes))));
+    assert!(matches!(SieveConfig::new(100).start(10).algorithm(Algorithm::Lmo).count(), Err(Error::Config(ConfigError::UnsupportedByLmo("a start")))));
+    assert_eq!(SieveConfig::new(100).threads(2).algorithm(Algorithm::Lmo).validate(), Err(ConfigError::UnsupportedByLmo("threads")));
+    assert_eq!(SieveConfig::new(100).cancel(CancelToken::new()).algorithm(Algorithm::Lmo).validate(), Err(ConfigError::UnsupportedByLmo("a cancel token")));
+    assert_eq!(ConfigError::ZeroThreads.to_string(), "the number of threads must be at least 1");
+
+    let primes = |config: SieveConfig| config.count().unwrap().primes;
+    assert_eq!(primes(SieveConfig::new(1_000_000)), 78498);
+    assert_eq!(primes(SieveConfig::new(100).start(50).threads(2).segment_bytes(64)), 10);
+    assert_eq!(primes(SieveConfig::new(100).start(98)), 0);
+    // Auto only uses LMO for large limits without settings of the sieve
+    assert_eq!(SieveConfig::new(LMO_THRESHOLD).chosen_algorithm(), Algorithm::Lmo);
+    assert_eq!(SieveConfig::new(LMO_THRESHOLD).threads(2).chosen_algorithm(), Algorithm::Sieve);
+    assert_eq!(SieveConfig::new(LMO_THRESHOLD - 1).chosen_algorithm(), Algorithm::Sieve);
+    assert_eq!(SieveConfig::new(100).algorithm(Algorithm::Lmo).chosen_algorithm(), Algorithm::Lmo);
+    assert!(matches!(SieveConfig::new(LMO_THRESHOLD).threads(0).segment_bytes(0).count(), Err(Error::Config(ConfigError::ZeroThreads))));
+    assert_eq!(primes(SieveConfig::new(1_000_000).algorithm(Algorithm::Lmo)), 78498);
+    assert_eq!(primes(SieveConfig::new(LMO_THRESHOLD)), primes(SieveConfig::new(LMO_THRESHOLD).algorithm(Algorithm::Lmo)));
+
+    // Settings only the sieve has make the sieve count large limits too
+    let calls: std::rc::Rc<std::cell::Cell<usize>> = std::rc::Rc::new(std::cell::Cell::new(0));
+    let hook: std::rc::Rc<std::cell::Cell<usize>> = std::rc::Rc::clone(&calls);
+    assert_eq!(primes(SieveConfig::new(LMO_THRESHOLD).progress(move |_| hook.set(hook.get() + 1))), primes(SieveConfig::new(LMO_THRESHOLD)));
+    assert!(calls.get() > 0);
+
+    // The hook gets the progress of the sieve, even with the algorithm chosen for the limit
+    let calls: std::rc::Rc<std::cell::Cell<usize>> = std::rc::Rc::new(std::cell::Cell::new(0));
+    let hook: std::rc::Rc<std::cell::Cell<usize>> = std::rc::Rc::clone(&calls);
+    let result: CountResult = SieveConfig::new(10_000_000).threads(2).segment_bytes(1 << 10)
+      .progress(move |_| hook.set(hook.get() + 1))
+      .count()
+      .unwrap();
+    assert_eq!(result, CountResult { primes: 664579, start: 2, end: 10_000_000, cancelled: false });
+    assert!(calls.get() > 1);
+  }
+
+  #[test]
+  fn test_count_primes_zero_threads() {
+    // A threads or cache of 0 uses the default one, only try_count_primes rejects them
+    assert_eq!(count_primes(100, None, Some(0), None), 25);
+    assert_eq!(count_primes(100, Some(50), Some(0), Some(0)), 10);
+  }
+
+  #[test]
+  fn test_errors() {
+    use std::error::Error as _;
+
+    // Settings that can't be used
+    assert!(matches!(try_count_primes(100, None, Some(0), None), Err(Error::Config(ConfigError::ZeroThreads))));
+    assert!(matches!(try_count_primes(100, Some(10), None, Some(0)), Err(Error::Config(ConfigError::ZeroSegmentBytes))));
+    assert!(matches!(count_primes_with(100, None, CountOptions { threads: Some(0), ..Default::default() }), Err(Error::Config(ConfigError::ZeroThreads))));
+    assert_eq!(try_count_primes(100, Some(50), Some(2), Some(64)).unwrap(), 10);
+    let err: Error = try_count_primes(100, None, Some(0), None).unwrap_err();
+    assert_eq!(err.to_string(), "invalid count settings: the number of threads must be at least 1");
+    assert!(err.source().is_some());
+
+    // A thread that panics fails the count instead of the calling thread
+    let handles: Vec<thread::JoinHandle<()>> = vec![thread::spawn(|| {}), thread::spawn(|| panic!("sieving thread"))];
+    assert!(matches!(join_threads(handles), Err(Error::ThreadPanicked)));
+    assert!(join_threads(vec![thread::spawn(|| {})]).is_ok());
+
+    // Shards of a range with fewer numbers, and checkpoints that can't be written
+    assert!(matches!(
+      count_primes_shard(10, Some(5), Shard::new(1, 7).unwrap(), CountOptions::default(), None),
+      Err(Error::TooManyShards { start: 5, limit: 10, shards: 7 })
+    ));
+    let path: std::path::PathBuf = std::env::temp_dir().join(format!("primeutils-test-{}-missing", std::process::id())).join("count.ckpt");
+    assert!(matches!(count_primes_resume(100, None, CountOptions::default(), &path), Err(Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound));
+
+    // 0 has no factorization, unlike 1
+    assert!(matches!(try_factorize(0), Err(Error::Zero)));
+    assert!(matches!(try_split_into_factors(0), Err(Error::Zero)));
+    assert_eq!(try_factorize(1).unwrap(), factorize(1));
+    assert_eq!(try_split_into_factors(360).unwrap(), vec![2, 2, 2, 3, 3, 5]);
+
+    // There is no 0th prime, and the primes past the greatest ones of 64 bits don't fit
+    assert!(matches!(try_nth_prime(0), Err(Error::Zero)));
+    assert!(matches!(try_nth_prime(usize::MAX), Err(Error::Overflow)));
+    assert_eq!(try_nth_prime(10).unwrap(), 29);
+    assert!(matches!(try_next_prime(18_446_744_073_709_551_557), Err(Error::Overflow)));
+    assert_eq!(try_next_prime(13).unwrap(), 17);
+
+    // Sieves of ranges too wide for the memory, and bytes that aren't a sieve
+    let out_of_memory = |result: Result<()>| matches!(result, Err(Error::OutOfMemory));
+    assert!(out_of_memory(BitSieve::try_new(0, usize::MAX).map(|_| ())));
+    assert!(out_of_memory(PrimeTable::build(std::env::temp_dir().join("primeutils-test-unwritten.table"), 0, usize::MAX)));
+    assert!(matches!(BitSieve::try_from_bytes(&[0; 10]), Err(Error::InvalidData(_))));
+    assert_eq!(BitSieve::try_from_bytes(&BitSieve::new(10, 100).to_bytes()).unwrap(), BitSieve::new(10, 100));
+    assert!(out_of_memory(try_vec(0u64, usize::MAX).map(|_| ())));
+    assert_eq!(try_primes_in_range(90, 110).unwrap().collect::<Vec<usize>>(), vec![97, 101, 103, 107, 109]);
+
+    // A sieve too small for the segment grows instead of failing
+    let primes: Vec<u32> = simple_sieve(1_000);
+    let mut sieve: Vec<u64> = Vec::new();
+    assert_eq!(segment_sieve(&mut sieve, &primes, 1_000, 1_000_000), 78498 - 168);
+
+    // Squares of primes near 2^32 are only crossed off if the square root of the limit isn't truncated
+    let prime: usize = 4_294_967_291;
+    let square: usize = prime * prime;
+    assert_eq!(try_count_primes(square, Some(square - 200), Some(1), None).unwrap(), (square - 200..=square).filter(|&x| is_prime(x as u64)).count());
+    assert_eq!(try_count_primes(square, Some(square), Some(1), None).unwrap(), 0);
+  }
+
+  // Compare the sieve storage widths, sieving and counting the same range with each one.
+  // Run it with `cargo test --release bench_storage_width -- --ignored --nocapture`
+  #[test]
+  #[ignore]
+  fn bench_storage_width() {
+
+    fn sieve_range<W: bits::Word>(primes: &[u32], low: usize, high: usize) -> (usize, std::time::Duration) {
+      let now: std::time::Instant = std::time::Instant::now();
+      let mut count: usize = 0;
+      let mut sieve: bucket::BucketSieve<W> = bucket::BucketSieve::new(primes, low, high, 1 << 15, 1 << 15);
+      while let Some((base, segment)) = sieve.next_segment() {
+        count += count_sieve(segment, base, u64::MAX);
+      }
+      (count, now.elapsed())
+    }
+
+    fn count_words<W: bits::Word>(sieve: &[W]) -> (usize, std::time::Duration) {
+      let now: std::time::Instant = std::time::Instant::now();
+      let count: usize = (0..1000).map(|_| count_sieve(std::hint::black_box(sieve), 0, u64::MAX)).sum();
+      (count, now.elapsed())
+    }
+
+    let (low, high): (usize, usize) = (1_000_000_000_000, 1_000_300_000_000);
+    let primes: Vec<u32> = simple_sieve(high.isqrt() as u32);
+    let (count_u8, time_u8) = sieve_range::<u8>(&primes, low, high);
+    let (count_u64, time_u64) = sieve_range::<u64>(&primes, low, high);
+    assert_eq!(count_u8, count_u64);
+    println!("Sieve {}..{}: u8 {:?}, u64 {:?}", low, high, time_u8, time_u64);
+
+    let bytes: Vec<u8> = (0..1 << 20).map(|i: u32| i.wrapping_mul(2_654_435_761) as u8).collect();
+    let words: Vec<u64> = bytes.chunks(8).map(u64::from_bytes).collect();
+    let (count_u8, time_u8) = count_words(&bytes);
+    let (count_u64, time_u64) = count_words(&words);
+    assert_eq!(count_u8, count_u64);
+    println!("Count 1 MiB 1000 times: u8 {:?}, u64 {:?}", time_u8, time_u64);
+  }
 }
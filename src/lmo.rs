@@ -0,0 +1,208 @@
+use crate::iter::PrimeIter;
+use crate::simple_sieve;
+
+// Below this limit it is faster to just sieve
+const SIEVE_LIMIT: usize = 1 << 16;
+
+// Integer cube root
+fn icbrt(x: usize) -> usize {
+
+  // Start from the float approximation and correct it
+  let mut root: usize = (x as f64).cbrt() as usize;
+  while root > 0 && root.checked_pow(3).is_none_or(|cube| cube > x) { root -= 1 }
+  while (root + 1).checked_pow(3).is_some_and(|cube| cube <= x) { root += 1 }
+
+  root
+}
+
+// Binary indexed tree counting the unsieved numbers of a segment
+struct Fenwick {
+  tree: Vec<u32>,
+}
+
+impl Fenwick {
+
+  // Create a tree with every position set to 1
+  fn new(size: usize) -> Fenwick {
+    let mut fenwick: Fenwick = Fenwick { tree: vec![0; size] };
+    fenwick.fill(size);
+    fenwick
+  }
+
+  // Set the first size positions to 1 and the rest to 0
+  fn fill(&mut self, size: usize) {
+    for i in 0..self.tree.len() {
+      // Each node covers the positions (i & (i + 1))..=i
+      let first: usize = i & (i + 1);
+      self.tree[i] = if i < size { (i - first + 1) as u32 } else { size.saturating_sub(first) as u32 };
+    }
+  }
+
+  // Set the position to 0. It must be 1 before
+  fn remove(&mut self, position: usize) {
+    let mut i: usize = position;
+    while i < self.tree.len() {
+      self.tree[i] -= 1;
+      i |= i + 1;
+    }
+  }
+
+  // Count how many positions up to (and including) position are set
+  fn count(&self, position: usize) -> u32 {
+    let mut count: u32 = 0;
+    let mut i: usize = position + 1;
+    while i > 0 {
+      count += self.tree[i - 1];
+      i &= i - 1;
+    }
+    count
+  }
+}
+
+// Get the least prime factor and the Möbius function of every number up to limit.
+// The least prime factor of 1 is set to usize::MAX
+fn lpf_and_mobius(limit: usize) -> (Vec<usize>, Vec<i8>) {
+
+  let mut lpf: Vec<usize> = vec![0; limit + 1];
+  let mut mu: Vec<i8> = vec![1; limit + 1];
+  lpf[1] = usize::MAX;
+
+  for i in 2..=limit {
+    // i is prime if no smaller prime divides it
+    if lpf[i] != 0 { continue }
+
+    let mut multiple: usize = i;
+    while multiple <= limit {
+      if lpf[multiple] == 0 { lpf[multiple] = i }
+      mu[multiple] = -mu[multiple];
+      multiple += i;
+    }
+
+    // Numbers divisible by the square of a prime have μ = 0
+    if let Some(square) = i.checked_mul(i) {
+      let mut multiple: usize = square;
+      while multiple <= limit {
+        mu[multiple] = 0;
+        multiple += square;
+      }
+    }
+  }
+
+  (lpf, mu)
+}
+
+// Contribution of the special leaves to φ(x, π(y)). This is computed by sieving
+// the numbers up to x / y in segments, removing one prime at a time, and counting
+// the numbers left below x / (p_b * m) with a binary indexed tree
+fn special_leaves(x: usize, y: usize, primes: &[u32], lpf: &[usize], mu: &[i8]) -> i128 {
+
+  let limit: usize = x / y + 1;
+  let segment_size: usize = std::cmp::max(limit.isqrt(), 1 << 16).next_power_of_two();
+
+  let mut sieve: Vec<bool> = vec![true; segment_size];
+  let mut tree: Fenwick = Fenwick::new(segment_size);
+  // Numbers left in the previous segments after removing the first b primes
+  let mut phi: Vec<i128> = vec![0; primes.len()];
+  // Next multiple of each prime to cross off
+  let mut next: Vec<usize> = primes.iter().map(|&prime| prime as usize).collect();
+
+  let mut sum: i128 = 0;
+  let mut low: usize = 1;
+
+  while low < limit {
+    let high: usize = std::cmp::min(low + segment_size, limit);
+    let size: usize = high - low;
+
+    sieve.fill(true);
+    tree.fill(size);
+
+    // For each prime p_b, the leaves are φ(x / (p_b * m), b - 1) with
+    // m <= y < p_b * m, the least prime factor of m greater than p_b and μ(m) != 0
+    for b in 0..primes.len().saturating_sub(1) {
+      let prime: usize = primes[b] as usize;
+      let min_m: usize = std::cmp::max(x / prime / high, y / prime);
+      let max_m: usize = std::cmp::min(x / prime / low, y);
+
+      // Only the larger m have greater least prime factors. Every next segment
+      // has a lower max_m, so the next primes aren't needed anymore either
+      if prime >= max_m { break }
+
+      for m in ((min_m + 1)..=max_m).rev() {
+        if mu[m] != 0 && prime < lpf[m] {
+          let count: u32 = tree.count(x / (prime * m) - low);
+          sum -= mu[m] as i128 * (phi[b] + count as i128);
+        }
+      }
+
+      phi[b] += tree.count(size - 1) as i128;
+
+      // Remove the multiples of the prime from this segment
+      let mut multiple: usize = next[b];
+      while multiple < high {
+        if sieve[multiple - low] {
+          sieve[multiple - low] = false;
+          tree.remove(multiple - low);
+        }
+        multiple += prime;
+      }
+      next[b] = multiple;
+    }
+
+    low = high;
+  }
+
+  sum
+}
+
+// Count the numbers up to x with exactly two prime factors, both greater than y
+fn p2(x: usize, y: usize, pi_y: usize) -> i128 {
+
+  let sqrt: usize = x.isqrt();
+  if y >= sqrt { return 0 }
+
+  // For each prime p in (y, sqrt(x)], π(x / p) is needed. Going through
+  // the primes backwards, x / p increases, so they can all be found while
+  // iterating once over the primes up to x / (y + 1)
+  let large_primes: Vec<usize> = PrimeIter::new(y + 1, sqrt).collect();
+  if large_primes.is_empty() { return 0 }
+
+  let mut sum: i128 = 0;
+  let mut pi: usize = 0;
+  let mut primes: std::iter::Peekable<PrimeIter> = PrimeIter::new(2, x / large_primes[0]).peekable();
+
+  for (i, &prime) in large_primes.iter().enumerate().rev() {
+    let target: usize = x / prime;
+    while primes.next_if(|&q| q <= target).is_some() {
+      pi += 1;
+    }
+
+    // π(x / p) - π(p) + 1, being p the (π(y) + i + 1)-th prime
+    sum += pi as i128 - (pi_y + i) as i128;
+  }
+
+  sum
+}
+
+// Count the prime numbers below or equal to x using the Lagarias-Miller-Odlyzko algorithm.
+// π(x) = φ(x, a) + a - 1 - P2(x, y), being a = π(y) and y a bit larger than x^(1/3)
+pub fn count_primes_lmo(x: usize) -> usize {
+
+  if x < SIEVE_LIMIT { return simple_sieve(x as u32).len() }
+
+  // A larger y reduces the numbers to sieve but increases the special leaves
+  let alpha: f64 = f64::max(1.0, (x as f64).ln() / 10.0);
+  let y: usize = std::cmp::min((icbrt(x) as f64 * alpha) as usize, x.isqrt());
+
+  let primes: Vec<u32> = simple_sieve(y as u32);
+  let pi_y: usize = primes.len();
+  let (lpf, mu) = lpf_and_mobius(y);
+
+  // Ordinary leaves: μ(n) * φ(x / n, 0) for every n up to y
+  let mut phi: i128 = mu.iter().enumerate().skip(1)
+    .map(|(n, &mu)| mu as i128 * (x / n) as i128)
+    .sum();
+
+  phi += special_leaves(x, y, &primes, &lpf, &mu);
+
+  (phi + pi_y as i128 - 1 - p2(x, y, pi_y)) as usize
+}
@@ -1,5 +1,13 @@
 use std::env;
+use std::io;
+use std::io::{IsTerminal, Write};
 use std::process;
+use std::time::Duration;
+
+// Address where the coordinator listens for workers if none is given
+const COORDINATOR_ADDRESS: &str = "127.0.0.1:7070";
+// Counts show their progress after this long, and update it at most this often
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
 
 // Count how many prime numbers are there below count_limit
 struct Count {
@@ -7,34 +15,158 @@ struct Count {
   count_start: Option<usize>,
   threads: Option<usize>,
   cache: Option<usize>,
+  resume: Option<String>,
+  shard: Option<primeutils::Shard>,
+}
+
+// List the prime numbers between list_start and list_limit
+struct List {
+  list_limit: usize,
+  list_start: Option<usize>,
+}
+
+// Count the prime numbers between start and limit with the workers connecting to address
+struct Coordinator {
+  limit: usize,
+  start: Option<usize>,
+  address: String,
+  timeout: Option<u64>,
+  size: Option<usize>,
+}
+
+// Count the ranges given by the coordinator at address
+struct Worker {
+  address: String,
+  threads: Option<usize>,
+  cache: Option<usize>,
+}
+
+// Find the fastest threads and cache and save them to the profile of the machine
+struct Tune {
+  limit: Option<usize>,
+  time: Option<u64>,
+  profile: Option<String>,
+}
+
+// Add the partial counts of the shards of a count saved in files
+struct Merge {
+  files: Vec<String>,
 }
 
 // Check if num is prime
 struct IsPrime {
-  num: u64,
+  num: u128,
+}
+
+// Find the prime numbers next to num
+struct Neighbor {
+  num: u128,
 }
 
 struct Factors {
   num: u64,
+  list: bool,
+}
+
+// Compute an arithmetic function of num
+struct Arithmetic {
+  num: u64,
+}
+
+struct DivisorSum {
+  num: u64,
+  k: u32,
 }
 
-struct GCD {
+struct Gcd {
   x: u64,
   y: u64,
 }
 
-struct LCM {
+struct Lcm {
   x: u64,
   y: u64,
 }
 
+// Sieve the numbers between table_start and table_limit and save the primes in file
+struct TableBuild {
+  file: String,
+  table_limit: usize,
+  table_start: Option<usize>,
+}
+
+// Answer a query with the prime numbers saved in file
+struct TableQuery {
+  file: String,
+  query: Query,
+}
+
+enum Query {
+  IsPrime(usize),
+  Count(Option<usize>, usize),
+  List(Option<usize>, usize),
+  Verify,
+}
+
 enum Arguments {
   Help(),
   Count(Count),
+  List(List),
+  Merge(Merge),
+  Coordinator(Coordinator),
+  Worker(Worker),
+  Tune(Tune),
   IsPrime(IsPrime),
+  Next(Neighbor),
+  Prev(Neighbor),
   Factors(Factors),
-  GCD(GCD),
-  LCM(LCM),
+  Divisors(Arithmetic),
+  DivisorCount(Arithmetic),
+  DivisorSum(DivisorSum),
+  Totient(Arithmetic),
+  Mobius(Arithmetic),
+  Radical(Arithmetic),
+  Carmichael(Arithmetic),
+  NthPrime(Arithmetic),
+  PrimePi(Arithmetic),
+  Gcd(Gcd),
+  Lcm(Lcm),
+  TableBuild(TableBuild),
+  TableQuery(TableQuery),
+  CpuInfo(),
+}
+
+// Parse "[START]..LIMIT" or "LIMIT" into its start and limit
+fn parse_range(arg: &str, name: &str) -> Result<(Option<usize>, usize), String> {
+  if arg.contains("..") {
+    let parts: Vec<&str> = arg.split("..").collect();
+
+    if parts.len() > 2 {
+      return Err(format!(r#"Error while parsing {name} start and limit: more than one delimiter ".." found"#));
+    }
+    else if parts.len() < 2 {
+      return Err(format!("Error while parsing {name} start and limit: limit missing"));
+    }
+
+    let val = parts[0].parse::<usize>();
+    if let Err(_error) = val { return Err(format!("Error while parsing {name} start: invalid number")); }
+    let start: usize = val.unwrap();
+
+    let val = parts[1].parse::<usize>();
+    if let Err(_error) = val { return Err(format!("Error while parsing {name} limit: invalid number")); }
+    let limit: usize = val.unwrap();
+
+    if start > limit {
+      return Err(format!("Error while parsing {name} start and limit: start is higher than limit"));
+    }
+
+    Ok((Some(start), limit))
+  }
+  else {
+    let val = arg.parse::<usize>();
+    if let Err(_error) = val { return Err(format!("Error while parsing {name} limit: invalid number")); }
+    Ok((None, val.unwrap()))
+  }
 }
 
 fn parse_count(args: Vec<String>) -> Result<Count, String> {
@@ -42,16 +174,18 @@ fn parse_count(args: Vec<String>) -> Result<Count, String> {
   let mut count_start: Option<usize> = None;
   let mut threads: Option<usize> = None;
   let mut cache: Option<usize> = None;
+  let mut resume: Option<String> = None;
+  let mut shard: Option<primeutils::Shard> = None;
   
   let mut i: usize = 0;
   while i < args.len() {
     let arg: &String = &args[i];
   
     if arg == "-t" {
-      if threads != None { return Err(String::from(r#"Value already set for the parameter "-t""#)) }
+      if threads.is_some() { return Err(String::from(r#"Value already set for the parameter "-t""#)) }
   
       let val: Option<&String> = args.get(i+1);
-      if let None = val { return Err(String::from(r#"Missing value for option "-t""#)) }
+      if val.is_none() { return Err(String::from(r#"Missing value for option "-t""#)) }
   
       let val: Result<usize, std::num::ParseIntError> = val.unwrap().parse::<usize>();
       if let Err(_error) = val { return Err(String::from(r#"Invalid value for option "-t""#)) }
@@ -60,10 +194,10 @@ fn parse_count(args: Vec<String>) -> Result<Count, String> {
       i += 1;
     }
     else if arg == "-s" {
-      if cache != None { return Err(String::from(r#"Value already set for the parameter "-s""#)) }
+      if cache.is_some() { return Err(String::from(r#"Value already set for the parameter "-s""#)) }
   
       let val: Option<&String> = args.get(i+1);
-      if let None = val { return Err(String::from(r#"Missing value for option "-s""#)) }
+      if val.is_none() { return Err(String::from(r#"Missing value for option "-s""#)) }
   
       let val: Result<usize, std::num::ParseIntError> = val.unwrap().parse::<usize>();
       if let Err(_error) = val { return Err(String::from(r#"Invalid value for option "-s""#)) }
@@ -71,46 +205,45 @@ fn parse_count(args: Vec<String>) -> Result<Count, String> {
       cache = Some(val.unwrap());
       i += 1;
     }
-    else if arg.starts_with("-") {
-      return Err(String::from(r#"Invalid option: ""#) + arg + &String::from(r#"""#));
+    else if arg == "--resume" {
+      if resume.is_some() { return Err(String::from(r#"Value already set for the parameter "--resume""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "--resume""#)) }
+
+      resume = Some(val.unwrap().clone());
+      i += 1;
     }
-    else {
-      if count_limit != None { return Err(String::from("Count limit already set!")); }
+    else if arg == "--shard" {
+      if shard.is_some() { return Err(String::from(r#"Value already set for the parameter "--shard""#)) }
 
-      if arg.contains("..") {
-        let parts: Vec<&str> = arg.split("..").collect();
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "--shard""#)) }
 
-        if parts.len() > 2 {
-          return Err(String::from(r#"Error while parsing count start and limit: more than one delimiter ".." found"#));
-        }
-        else if parts.len() < 2 {
-          return Err(String::from("Error while parsing count start and limit: limit missing"));
-        }
-        else {
-          let val = parts[0].parse::<usize>();
-          if let Err(_error) = val { return Err(String::from("Error while parsing count start: invalid number")); }
-          count_start = Some(val.unwrap());
+      // "i/n", with i between 1 and n
+      let val: Option<primeutils::Shard> = val.unwrap().split_once('/')
+        .and_then(|(index, count)| Some((index.parse::<usize>().ok()?, count.parse::<usize>().ok()?)))
+        .and_then(|(index, count)| primeutils::Shard::new(index, count));
+      if val.is_none() { return Err(String::from(r#"Invalid value for option "--shard""#)) }
 
-          let val = parts[1].parse::<usize>();
-          if let Err(_error) = val { return Err(String::from("Error while parsing count limit: invalid number")); }
-          count_limit = Some(val.unwrap());
+      shard = val;
+      i += 1;
+    }
+    else if arg.starts_with("-") {
+      return Err(String::from(r#"Invalid option: ""#) + arg + &String::from(r#"""#));
+    }
+    else {
+      if count_limit.is_some() { return Err(String::from("Count limit already set!")); }
 
-          if count_start > count_limit {
-            return Err(String::from("Error while parsing count start and limit: start is higher than limit"));
-          }
-        }
-      }
-      else {
-        let val = arg.parse::<usize>();
-        if let Err(_error) = val { return Err(String::from("Error while parsing count limit: invalid number")); }
-        count_limit = Some(val.unwrap());
-      }
+      let (start, limit) = parse_range(arg, "count")?;
+      count_start = start;
+      count_limit = Some(limit);
     }
   
     i += 1;
   }
   
-  if let None = count_limit {
+  if count_limit.is_none() {
     return Err(String::from("The count limit should be specified!"));
   }
   
@@ -119,19 +252,253 @@ fn parse_count(args: Vec<String>) -> Result<Count, String> {
     count_start,
     threads,
     cache,
+    resume,
+    shard,
+  })
+}
+
+fn parse_list(args: Vec<String>) -> Result<List, String> {
+  let mut list_limit: Option<usize> = None;
+  let mut list_start: Option<usize> = None;
+
+  let mut i: usize = 0;
+  while i < args.len() {
+    let arg: &String = &args[i];
+
+    if arg.starts_with("-") {
+      return Err(String::from(r#"Invalid option: ""#) + arg + &String::from(r#"""#));
+    }
+
+    if list_limit.is_some() { return Err(String::from("List limit already set!")); }
+
+    let (start, limit) = parse_range(arg, "list")?;
+    list_start = start;
+    list_limit = Some(limit);
+
+    i += 1;
+  }
+
+  if list_limit.is_none() {
+    return Err(String::from("The list limit should be specified!"));
+  }
+
+  Ok(List {
+    list_limit: list_limit.unwrap(),
+    list_start,
+  })
+}
+
+fn parse_merge(args: Vec<String>) -> Result<Merge, String> {
+
+  if let Some(arg) = args.iter().find(|arg| arg.starts_with("-")) {
+    return Err(String::from(r#"Invalid option: ""#) + arg + &String::from(r#"""#));
+  }
+  if args.is_empty() {
+    return Err(String::from("The files to merge should be specified!"));
+  }
+
+  Ok(Merge {
+    files: args,
+  })
+}
+
+fn parse_coordinator(args: Vec<String>) -> Result<Coordinator, String> {
+  let mut limit: Option<usize> = None;
+  let mut start: Option<usize> = None;
+  let mut address: Option<String> = None;
+  let mut timeout: Option<u64> = None;
+  let mut size: Option<usize> = None;
+
+  let mut i: usize = 0;
+  while i < args.len() {
+    let arg: &String = &args[i];
+
+    if arg == "--listen" {
+      if address.is_some() { return Err(String::from(r#"Value already set for the parameter "--listen""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "--listen""#)) }
+
+      address = Some(val.unwrap().clone());
+      i += 1;
+    }
+    else if arg == "--timeout" {
+      if timeout.is_some() { return Err(String::from(r#"Value already set for the parameter "--timeout""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "--timeout""#)) }
+
+      let val: Result<u64, std::num::ParseIntError> = val.unwrap().parse::<u64>();
+      if let Err(_error) = val { return Err(String::from(r#"Invalid value for option "--timeout""#)) }
+
+      timeout = Some(val.unwrap());
+      i += 1;
+    }
+    else if arg == "--size" {
+      if size.is_some() { return Err(String::from(r#"Value already set for the parameter "--size""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "--size""#)) }
+
+      let val: Result<usize, std::num::ParseIntError> = val.unwrap().parse::<usize>();
+      if let Err(_error) = val { return Err(String::from(r#"Invalid value for option "--size""#)) }
+
+      size = Some(val.unwrap());
+      i += 1;
+    }
+    else if arg.starts_with("-") {
+      return Err(String::from(r#"Invalid option: ""#) + arg + &String::from(r#"""#));
+    }
+    else {
+      if limit.is_some() { return Err(String::from("Count limit already set!")); }
+
+      let (range_start, range_limit) = parse_range(arg, "count")?;
+      start = range_start;
+      limit = Some(range_limit);
+    }
+
+    i += 1;
+  }
+
+  if limit.is_none() {
+    return Err(String::from("The count limit should be specified!"));
+  }
+
+  Ok(Coordinator {
+    limit: limit.unwrap(),
+    start,
+    address: address.unwrap_or(String::from(COORDINATOR_ADDRESS)),
+    timeout,
+    size,
+  })
+}
+
+fn parse_worker(args: Vec<String>) -> Result<Worker, String> {
+  let mut address: Option<String> = None;
+  let mut threads: Option<usize> = None;
+  let mut cache: Option<usize> = None;
+
+  let mut i: usize = 0;
+  while i < args.len() {
+    let arg: &String = &args[i];
+
+    if arg == "--connect" {
+      if address.is_some() { return Err(String::from(r#"Value already set for the parameter "--connect""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "--connect""#)) }
+
+      address = Some(val.unwrap().clone());
+      i += 1;
+    }
+    else if arg == "-t" {
+      if threads.is_some() { return Err(String::from(r#"Value already set for the parameter "-t""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "-t""#)) }
+
+      let val: Result<usize, std::num::ParseIntError> = val.unwrap().parse::<usize>();
+      if let Err(_error) = val { return Err(String::from(r#"Invalid value for option "-t""#)) }
+
+      threads = Some(val.unwrap());
+      i += 1;
+    }
+    else if arg == "-s" {
+      if cache.is_some() { return Err(String::from(r#"Value already set for the parameter "-s""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "-s""#)) }
+
+      let val: Result<usize, std::num::ParseIntError> = val.unwrap().parse::<usize>();
+      if let Err(_error) = val { return Err(String::from(r#"Invalid value for option "-s""#)) }
+
+      cache = Some(val.unwrap());
+      i += 1;
+    }
+    else {
+      return Err(String::from(r#"Invalid option: ""#) + arg + &String::from(r#"""#));
+    }
+
+    i += 1;
+  }
+
+  if address.is_none() {
+    return Err(String::from(r#"The address of the coordinator should be specified with "--connect"!"#));
+  }
+
+  Ok(Worker {
+    address: address.unwrap(),
+    threads,
+    cache,
+  })
+}
+
+fn parse_tune(args: Vec<String>) -> Result<Tune, String> {
+  let mut limit: Option<usize> = None;
+  let mut time: Option<u64> = None;
+  let mut profile: Option<String> = None;
+
+  let mut i: usize = 0;
+  while i < args.len() {
+    let arg: &String = &args[i];
+
+    if arg == "--limit" {
+      if limit.is_some() { return Err(String::from(r#"Value already set for the parameter "--limit""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "--limit""#)) }
+
+      let val: Result<usize, std::num::ParseIntError> = val.unwrap().parse::<usize>();
+      if let Err(_error) = val { return Err(String::from(r#"Invalid value for option "--limit""#)) }
+
+      limit = Some(val.unwrap());
+      i += 1;
+    }
+    else if arg == "--time" {
+      if time.is_some() { return Err(String::from(r#"Value already set for the parameter "--time""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "--time""#)) }
+
+      let val: Result<u64, std::num::ParseIntError> = val.unwrap().parse::<u64>();
+      if let Err(_error) = val { return Err(String::from(r#"Invalid value for option "--time""#)) }
+
+      time = Some(val.unwrap());
+      i += 1;
+    }
+    else if arg == "--profile" {
+      if profile.is_some() { return Err(String::from(r#"Value already set for the parameter "--profile""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "--profile""#)) }
+
+      profile = Some(val.unwrap().clone());
+      i += 1;
+    }
+    else {
+      return Err(String::from(r#"Invalid option: ""#) + arg + &String::from(r#"""#));
+    }
+
+    i += 1;
+  }
+
+  Ok(Tune {
+    limit,
+    time,
+    profile,
   })
 }
 
 fn parse_is_prime(args: Vec<String>) -> Result<IsPrime, String> {
-  let mut num: Option<u64> = None;
+  let mut num: Option<u128> = None;
 
   let mut i: usize = 0;
   while i < args.len() {
     let arg: &String = &args[i];
 
-    if num != None { return Err(String::from("Number to check already set!")); }
+    if num.is_some() { return Err(String::from("Number to check already set!")); }
   
-    let val = arg.parse::<u64>();
+    let val = arg.parse::<u128>();
     if let Err(_error) = val { return Err(String::from("Error while parsing number to check: invalid number")); }
 
     num = Some(val.unwrap());
@@ -139,7 +506,7 @@ fn parse_is_prime(args: Vec<String>) -> Result<IsPrime, String> {
     i += 1;
   }
 
-  if let None = num {
+  if num.is_none() {
     return Err(String::from("The number to check should be specified!"));
   }
   
@@ -148,33 +515,141 @@ fn parse_is_prime(args: Vec<String>) -> Result<IsPrime, String> {
   })
 }
 
+fn parse_neighbor(args: Vec<String>) -> Result<Neighbor, String> {
+  let mut num: Option<u128> = None;
+
+  let mut i: usize = 0;
+  while i < args.len() {
+    let arg: &String = &args[i];
+
+    if num.is_some() { return Err(String::from("Number to start from already set!")); }
+
+    let val = arg.parse::<u128>();
+    if let Err(_error) = val { return Err(String::from("Error while parsing number to start from: invalid number")); }
+
+    num = Some(val.unwrap());
+
+    i += 1;
+  }
+
+  if num.is_none() {
+    return Err(String::from("The number to start from should be specified!"));
+  }
+
+  Ok(Neighbor {
+    num: num.unwrap(),
+  })
+}
+
 fn parse_factors(args: Vec<String>) -> Result<Factors, String> {
   let mut num: Option<u64> = None;
+  let mut list: bool = false;
   
   let mut i: usize = 0;
   while i < args.len() {
     let arg: &String = &args[i];
 
-    if num != None { return Err(String::from("Number to split already set!")); }
+    if arg == "-l" {
+      if list { return Err(String::from(r#"Option "-l" already set"#)) }
+      list = true;
+    }
+    else if arg.starts_with("-") {
+      return Err(String::from(r#"Invalid option: ""#) + arg + &String::from(r#"""#));
+    }
+    else {
+      if num.is_some() { return Err(String::from("Number to split already set!")); }
     
-    let val = arg.parse::<u64>();
-    if let Err(_error) = val { return Err(String::from("Error while parsing number to split: invalid number")); }
+      let val = arg.parse::<u64>();
+      if let Err(_error) = val { return Err(String::from("Error while parsing number to split: invalid number")); }
   
-    num = Some(val.unwrap());
+      num = Some(val.unwrap());
+    }
 
     i += 1;
   }
 
-  if let None = num {
+  if num.is_none() {
     return Err(String::from("The number to split should be specified!"));
   }
     
   Ok(Factors {
     num: num.unwrap(),
+    list,
+  })
+}
+
+fn parse_arithmetic(args: Vec<String>) -> Result<Arithmetic, String> {
+  let mut num: Option<u64> = None;
+
+  let mut i: usize = 0;
+  while i < args.len() {
+    let arg: &String = &args[i];
+
+    if num.is_some() { return Err(String::from("Number to compute already set!")); }
+
+    let val = arg.parse::<u64>();
+    if let Err(_error) = val { return Err(String::from("Error while parsing number to compute: invalid number")); }
+
+    num = Some(val.unwrap());
+
+    i += 1;
+  }
+
+  if num.is_none() {
+    return Err(String::from("The number to compute should be specified!"));
+  }
+
+  Ok(Arithmetic {
+    num: num.unwrap(),
   })
 }
 
-fn parse_gcd(args: Vec<String>) -> Result<GCD, String> {
+fn parse_divisor_sum(args: Vec<String>) -> Result<DivisorSum, String> {
+  let mut num: Option<u64> = None;
+  let mut k: Option<u32> = None;
+
+  let mut i: usize = 0;
+  while i < args.len() {
+    let arg: &String = &args[i];
+
+    if arg == "-k" {
+      if k.is_some() { return Err(String::from(r#"Value already set for the parameter "-k""#)) }
+
+      let val: Option<&String> = args.get(i+1);
+      if val.is_none() { return Err(String::from(r#"Missing value for option "-k""#)) }
+
+      let val: Result<u32, std::num::ParseIntError> = val.unwrap().parse::<u32>();
+      if let Err(_error) = val { return Err(String::from(r#"Invalid value for option "-k""#)) }
+
+      k = Some(val.unwrap());
+      i += 1;
+    }
+    else if arg.starts_with("-") {
+      return Err(String::from(r#"Invalid option: ""#) + arg + &String::from(r#"""#));
+    }
+    else {
+      if num.is_some() { return Err(String::from("Number to compute already set!")); }
+
+      let val = arg.parse::<u64>();
+      if let Err(_error) = val { return Err(String::from("Error while parsing number to compute: invalid number")); }
+
+      num = Some(val.unwrap());
+    }
+
+    i += 1;
+  }
+
+  if num.is_none() {
+    return Err(String::from("The number to compute should be specified!"));
+  }
+
+  Ok(DivisorSum {
+    num: num.unwrap(),
+    k: k.unwrap_or(1),
+  })
+}
+
+fn parse_gcd(args: Vec<String>) -> Result<Gcd, String> {
   let mut x: Option<u64> = None;
   let mut y: Option<u64> = None;
 
@@ -182,35 +657,35 @@ fn parse_gcd(args: Vec<String>) -> Result<GCD, String> {
   while i < args.len() {
     let arg: &String = &args[i];
 
-    if x != None && y != None { return Err(String::from("Numbers to compute already set!")); }
+    if x.is_some() && y.is_some() { return Err(String::from("Numbers to compute already set!")); }
   
     let val = arg.parse::<u64>();
     if let Err(_error) = val { return Err(String::from("Error while parsing number to compute: invalid number")); }
 
-    if let None = x {
+    if x.is_none() {
       x = Some(val.unwrap());
     }
-    else if let None = y {
+    else if y.is_none() {
       y = Some(val.unwrap());
     }
 
     i += 1;
   }
 
-  if let None = x {
+  if x.is_none() {
     return Err(String::from("The numbers to compute should be specified!"));
   }
-  if let None = y {
+  if y.is_none() {
     return Err(String::from("Two numbers to compute should be specified!"));
   }
   
-  Ok(GCD {
+  Ok(Gcd {
     x: x.unwrap(),
     y: y.unwrap(),
   })
 }
 
-fn parse_lcm(args: Vec<String>) -> Result<LCM, String> {
+fn parse_lcm(args: Vec<String>) -> Result<Lcm, String> {
   let mut x: Option<u64> = None;
   let mut y: Option<u64> = None;
 
@@ -218,37 +693,123 @@ fn parse_lcm(args: Vec<String>) -> Result<LCM, String> {
   while i < args.len() {
     let arg: &String = &args[i];
 
-    if x != None && y != None { return Err(String::from("Numbers to compute already set!")); }
+    if x.is_some() && y.is_some() { return Err(String::from("Numbers to compute already set!")); }
   
     let val = arg.parse::<u64>();
     if let Err(_error) = val { return Err(String::from("Error while parsing number to compute: invalid number")); }
 
-    if let None = x {
+    if x.is_none() {
       x = Some(val.unwrap());
     }
-    else if let None = y {
+    else if y.is_none() {
       y = Some(val.unwrap());
     }
 
     i += 1;
   }
 
-  if let None = x {
+  if x.is_none() {
     return Err(String::from("The numbers to compute should be specified!"));
   }
-  if let None = y {
+  if y.is_none() {
     return Err(String::from("Two numbers to compute should be specified!"));
   }
   
-  Ok(LCM {
+  Ok(Lcm {
     x: x.unwrap(),
     y: y.unwrap(),
   })
 }
 
+fn parse_table_build(args: Vec<String>) -> Result<TableBuild, String> {
+  let mut file: Option<String> = None;
+  let mut table_limit: Option<usize> = None;
+  let mut table_start: Option<usize> = None;
+
+  let mut i: usize = 0;
+  while i < args.len() {
+    let arg: &String = &args[i];
+
+    if arg.starts_with("-") {
+      return Err(String::from(r#"Invalid option: ""#) + arg + &String::from(r#"""#));
+    }
+    else if table_limit.is_none() {
+      let (start, limit) = parse_range(arg, "table")?;
+      table_start = start;
+      table_limit = Some(limit);
+    }
+    else {
+      if file.is_some() { return Err(String::from("Table file already set!")); }
+      file = Some(arg.clone());
+    }
+
+    i += 1;
+  }
+
+  if table_limit.is_none() {
+    return Err(String::from("The table limit should be specified!"));
+  }
+  if file.is_none() {
+    return Err(String::from("The table file should be specified!"));
+  }
+
+  Ok(TableBuild {
+    file: file.unwrap(),
+    table_limit: table_limit.unwrap(),
+    table_start,
+  })
+}
+
+fn parse_table_query(args: Vec<String>) -> Result<TableQuery, String> {
+
+  if args.is_empty() {
+    return Err(String::from("The table file should be specified!"));
+  }
+  if args.len() < 2 {
+    return Err(String::from("The query should be specified!"));
+  }
+
+  // Checking the file is the only query without an argument
+  if args[1] == "verify" {
+    if args.len() > 2 { return Err(String::from("Only one query can be answered!")) }
+    return Ok(TableQuery {
+      file: args[0].clone(),
+      query: Query::Verify,
+    });
+  }
+  if args.len() < 3 {
+    return Err(String::from("The query should be specified!"));
+  }
+  if args.len() > 3 {
+    return Err(String::from("Only one query can be answered!"));
+  }
+
+  let query: Query = if args[1] == "is_prime" {
+    let val = args[2].parse::<usize>();
+    if let Err(_error) = val { return Err(String::from("Error while parsing number to check: invalid number")); }
+    Query::IsPrime(val.unwrap())
+  }
+  else if args[1] == "count" {
+    let (start, limit) = parse_range(&args[2], "count")?;
+    Query::Count(start, limit)
+  }
+  else if args[1] == "list" {
+    let (start, limit) = parse_range(&args[2], "list")?;
+    Query::List(start, limit)
+  }
+  else {
+    return Err(String::from(r#"Invalid query: ""#) + &args[1] + &String::from(r#"""#));
+  };
+
+  Ok(TableQuery {
+    file: args[0].clone(),
+    query,
+  })
+}
+
 fn parse_arguments(mut args: Vec<String>) -> Result<Arguments, String> {
   
-  if args.len() <= 0 {
+  if args.is_empty() {
     return Ok(Arguments::Help());
   }
 
@@ -261,23 +822,108 @@ fn parse_arguments(mut args: Vec<String>) -> Result<Arguments, String> {
   }
   // Count primes below a limit
   else if command == "count" {
-    parse_count(args).map(|argument| Arguments::Count(argument)).map_err(|err| String::from(r#"Command "count" arguments: "#) + &err)
+    parse_count(args).map(Arguments::Count).map_err(|err| String::from(r#"Command "count" arguments: "#) + &err)
+  }
+  // List the primes in a range
+  else if command == "list" {
+    parse_list(args).map(Arguments::List).map_err(|err| String::from(r#"Command "list" arguments: "#) + &err)
+  }
+  // Add the partial counts of a sharded count
+  else if command == "merge" {
+    parse_merge(args).map(Arguments::Merge).map_err(|err| String::from(r#"Command "merge" arguments: "#) + &err)
+  }
+  // Hand out the ranges of a count to workers
+  else if command == "serve-coordinator" {
+    parse_coordinator(args).map(Arguments::Coordinator).map_err(|err| String::from(r#"Command "serve-coordinator" arguments: "#) + &err)
+  }
+  // Count the ranges given by a coordinator
+  else if command == "worker" {
+    parse_worker(args).map(Arguments::Worker).map_err(|err| String::from(r#"Command "worker" arguments: "#) + &err)
+  }
+  // Find the fastest threads and cache for this machine
+  else if command == "tune" {
+    parse_tune(args).map(Arguments::Tune).map_err(|err| String::from(r#"Command "tune" arguments: "#) + &err)
   }
   // Check if a number is prime
   else if command == "is_prime" {
-    parse_is_prime(args).map(|argument| Arguments::IsPrime(argument)).map_err(|err| String::from(r#"Command "is_prime" arguments: "#) + &err)
+    parse_is_prime(args).map(Arguments::IsPrime).map_err(|err| String::from(r#"Command "is_prime" arguments: "#) + &err)
+  }
+  // Find the next prime number
+  else if command == "next" {
+    parse_neighbor(args).map(Arguments::Next).map_err(|err| String::from(r#"Command "next" arguments: "#) + &err)
+  }
+  // Find the previous prime number
+  else if command == "prev" {
+    parse_neighbor(args).map(Arguments::Prev).map_err(|err| String::from(r#"Command "prev" arguments: "#) + &err)
   }
   // Split a number into its prime factors
   else if command == "factors" {
-    parse_factors(args).map(|argument| Arguments::Factors(argument)).map_err(|err| String::from(r#"Command "factors" arguments: "#) + &err)
+    parse_factors(args).map(Arguments::Factors).map_err(|err| String::from(r#"Command "factors" arguments: "#) + &err)
+  }
+  // List the divisors of a number
+  else if command == "divisors" {
+    parse_arithmetic(args).map(Arguments::Divisors).map_err(|err| String::from(r#"Command "divisors" arguments: "#) + &err)
+  }
+  // Count the divisors of a number
+  else if command == "divisor_count" {
+    parse_arithmetic(args).map(Arguments::DivisorCount).map_err(|err| String::from(r#"Command "divisor_count" arguments: "#) + &err)
+  }
+  // Sum the powers of the divisors of a number
+  else if command == "divisor_sum" {
+    parse_divisor_sum(args).map(Arguments::DivisorSum).map_err(|err| String::from(r#"Command "divisor_sum" arguments: "#) + &err)
+  }
+  // Euler's totient function
+  else if command == "totient" {
+    parse_arithmetic(args).map(Arguments::Totient).map_err(|err| String::from(r#"Command "totient" arguments: "#) + &err)
+  }
+  // Möbius function
+  else if command == "mobius" {
+    parse_arithmetic(args).map(Arguments::Mobius).map_err(|err| String::from(r#"Command "mobius" arguments: "#) + &err)
+  }
+  // Radical of a number
+  else if command == "radical" {
+    parse_arithmetic(args).map(Arguments::Radical).map_err(|err| String::from(r#"Command "radical" arguments: "#) + &err)
+  }
+  // Carmichael's function
+  else if command == "carmichael" {
+    parse_arithmetic(args).map(Arguments::Carmichael).map_err(|err| String::from(r#"Command "carmichael" arguments: "#) + &err)
+  }
+  // Find the n-th prime number
+  else if command == "nth_prime" {
+    parse_arithmetic(args).map(Arguments::NthPrime).map_err(|err| String::from(r#"Command "nth_prime" arguments: "#) + &err)
+  }
+  // Count the prime numbers up to a number
+  else if command == "prime_pi" {
+    parse_arithmetic(args).map(Arguments::PrimePi).map_err(|err| String::from(r#"Command "prime_pi" arguments: "#) + &err)
   }
   // Split a number into its prime factors
   else if command == "gcd" {
-    parse_gcd(args).map(|argument| Arguments::GCD(argument)).map_err(|err| String::from(r#"Command "gcd" arguments: "#) + &err)
+    parse_gcd(args).map(Arguments::Gcd).map_err(|err| String::from(r#"Command "gcd" arguments: "#) + &err)
   }
   // Split a number into its prime factors
   else if command == "lcm" {
-    parse_lcm(args).map(|argument| Arguments::LCM(argument)).map_err(|err| String::from(r#"Command "lcm" arguments: "#) + &err)
+    parse_lcm(args).map(Arguments::Lcm).map_err(|err| String::from(r#"Command "lcm" arguments: "#) + &err)
+  }
+  // Save the primes in a range to a file
+  else if command == "table" && args.first().is_some_and(|sub| sub == "build") {
+    args.remove(0);
+    parse_table_build(args).map(Arguments::TableBuild).map_err(|err| String::from(r#"Command "table build" arguments: "#) + &err)
+  }
+  // Query the primes saved in a file
+  else if command == "table" && args.first().is_some_and(|sub| sub == "query") {
+    args.remove(0);
+    parse_table_query(args).map(Arguments::TableQuery).map_err(|err| String::from(r#"Command "table query" arguments: "#) + &err)
+  }
+  else if command == "table" {
+    Err(String::from(r#"Command "table" arguments: The subcommand should be "build" or "query"!"#))
+  }
+  // Show the threads and caches used to sieve
+  else if command == "cpu" && args.first().is_some_and(|sub| sub == "info") {
+    if args.len() > 1 { return Err(String::from(r#"Command "cpu info" arguments: Too many arguments!"#)) }
+    Ok(Arguments::CpuInfo())
+  }
+  else if command == "cpu" {
+    Err(String::from(r#"Command "cpu" arguments: The subcommand should be "info"!"#))
   }
   // Invalid command
   else {
@@ -285,25 +931,103 @@ fn parse_arguments(mut args: Vec<String>) -> Result<Arguments, String> {
   }
 }
 
+// Callback showing the progress of a count in a line of stderr, or None if
+// stderr is not a terminal (so the line doesn't end up in a log)
+fn progress_line() -> Option<primeutils::ProgressCallback> {
+  if !io::stderr().is_terminal() { return None }
+
+  let mut last: Duration = Duration::ZERO;
+  Some(Box::new(move |progress: &primeutils::Progress| {
+    if progress.elapsed < last + PROGRESS_INTERVAL { return }
+    last = progress.elapsed;
+
+    eprint!(
+      "\r\x1b[KSieved {} of {} chunks ({:.1}%), {} primes found, {:.0} s elapsed",
+      progress.done, progress.total, 100.0 * progress.done as f64 / progress.total as f64,
+      progress.primes, progress.elapsed.as_secs_f64()
+    );
+  }))
+}
+
 fn print_help() {
   println!("\
 Usage: primes-rs COMMAND REQUIRED_OPTIONS [OPTIONAL_OPTIONS]
 Commands and its options:
   help               Display this help.
   count              Count how many prime numbers there are between start and limit.
+                     Limits above 2^30 without start, -t or -s are counted with the LMO algorithm.
     [START]..LIMIT     Set the start and limit of the sieve (separated by \"..\").
     [-t NUM]           How many threads should be used to sieve.
-    [-s NUM]           How much cache should be used to sieve.
+    [-s NUM]           How much L1 cache (in bytes) should be used to sieve.
+    [--resume FILE]    Save the progress to a file from time to time, and resume from it if it exists.
+                       The count is always sieved, as it can't be resumed with the LMO algorithm.
+    [--shard I/N]      Count only the I-th of N parts of the range (from 1 to N), and print the
+                       result as a partial count to merge with the other parts. It is always sieved.
+  list               Print the prime numbers between start and limit, one per line.
+    [START]..LIMIT     Set the start and limit of the sieve (separated by \"..\").
+  merge              Add the partial counts of every part of a count, checking that they cover it exactly once.
+    FILE...             The files with the partial counts printed by count --shard.
+  serve-coordinator  Count the prime numbers between start and limit with workers, giving each one a range at a time over TCP.
+    [START]..LIMIT     Set the start and limit of the count (separated by \"..\").
+    [--listen ADDR]    The address to listen for workers (127.0.0.1:7070 by default).
+    [--timeout SECS]   How long a worker has to count a range before it is given to another one (600 by default).
+    [--size NUM]       How many numbers there are in each range.
+  worker             Count the ranges given by a coordinator until the count is finished.
+    --connect ADDR     The address of the coordinator.
+    [-t NUM]           How many threads should be used to sieve.
+    [-s NUM]           How much L1 cache (in bytes) should be used to sieve.
+  tune               Time short counts with several threads and cache sizes, and save the fastest ones to the
+                     profile of the machine, used by the counts that don't set them.
+    [--limit NUM]      Time the counts of a range above this number (10^12 by default).
+    [--time MS]        How long each timed count should take (100 by default).
+    [--profile FILE]   The file to save the profile to (PRIMEUTILS_PROFILE, or ~/.config/primeutils/profile by default).
   is_prime           Check if num is prime.
-    NUM                 The num to check.
+    NUM                 The num to check (up to 128 bits).
+  next               Find the lowest prime number greater than num.
+    NUM                 The num to start from (up to 128 bits).
+  prev               Find the greatest prime number lower than num.
+    NUM                 The num to start from (up to 128 bits).
   factors            Split num into its prime factors.
     NUM                 The num to split.
+    [-l]                List every factor instead of grouping them by exponent.
+  divisors           List all the divisors of num.
+    NUM                 The num to compute.
+  divisor_count      Count the divisors of num.
+    NUM                 The num to compute.
+  divisor_sum        Sum the k-th powers of the divisors of num.
+    NUM                 The num to compute.
+    [-k NUM]            The power of the divisors (1 by default).
+  totient            Count the numbers up to num that are coprime to it (Euler's totient).
+    NUM                 The num to compute.
+  mobius             Get the Möbius function of num.
+    NUM                 The num to compute.
+  radical            Get the product of the distinct prime factors of num.
+    NUM                 The num to compute.
+  carmichael         Get the Carmichael function of num.
+    NUM                 The num to compute.
+  nth_prime          Find the n-th prime number (starting at 1 for 2).
+    N                   The index of the prime.
+  prime_pi           Count how many prime numbers there are up to num, and so the index of num if it is prime.
+    NUM                 The num to compute.
   gcd                Get the greatest common divisor of two numbers.
     X                   One number.
     Y                   The other number.
   lcm                Get the least common multiple of two numbers.
     X                   One number.
-    Y                   The first number.\
+    Y                   The first number.
+  table build        Sieve the numbers between start and limit and save the prime numbers in a file.
+    [START]..LIMIT     Set the start and limit of the sieve (separated by \"..\").
+    FILE                The file to save the table to.
+  table query        Answer a query with the prime numbers saved in a file, without sieving again.
+    FILE                The file of the table.
+    is_prime NUM        Check if num is prime.
+    count [START]..LIMIT
+                        Count the prime numbers between start (the table start by default) and limit.
+    list [START]..LIMIT
+                        Print the prime numbers between start (the table start by default) and limit.
+    verify              Check the checksum of the whole file, which opening it doesn't.
+  cpu info           Show the threads and the caches used to sieve, and where the threads come from
+                     (PRIMEUTILS_THREADS, the cgroup CPU quota, the CPU affinity or the CPUs of the machine).\
 ");
 }
 
@@ -322,7 +1046,54 @@ fn main() {
     },
 
     Arguments::Count(count) => {
-      let primes: usize = primeutils::count_primes(count.count_limit, count.count_start, count.threads, count.cache);
+      let options: primeutils::CountOptions = primeutils::CountOptions {
+        threads: count.threads,
+        cache: count.cache,
+        progress: progress_line(),
+        cancel: None,
+      };
+
+      // A part of a count is printed to be read by merge
+      if let Some(shard) = count.shard {
+        let partial: primeutils::PartialCount = primeutils::count_primes_shard(
+          count.count_limit, count.count_start, shard, options, count.resume.as_deref().map(std::path::Path::new)
+        ).unwrap_or_else(|err| {
+          eprintln!("Problem counting the shard {}/{}:\n{err}", shard.index(), shard.count());
+          process::exit(1);
+        });
+
+        if io::stderr().is_terminal() { eprint!("\r\x1b[K") }
+        print!("{}", partial.to_text());
+        return;
+      }
+
+      let primes: usize = if let Some(file) = &count.resume {
+        primeutils::count_primes_resume(count.count_limit, count.count_start, options, std::path::Path::new(file))
+          .unwrap_or_else(|err| {
+            eprintln!("Problem with the checkpoint {}:\n{err}", file);
+            process::exit(1);
+          })
+          .primes
+      }
+      // Large limits from 0 are counted with LMO unless a setting of the sieve is given. The
+      // progress line is only shown when sieving, so it doesn't make them sieved too
+      else {
+        let mut config: primeutils::SieveConfig = primeutils::SieveConfig::new(count.count_limit);
+        if let Some(start) = count.count_start { config = config.start(start) }
+        if let Some(threads) = count.threads { config = config.threads(threads) }
+        if let Some(cache) = count.cache { config = config.segment_bytes(cache) }
+        if config.chosen_algorithm() == primeutils::Algorithm::Sieve {
+          if let Some(progress) = options.progress { config = config.progress(progress) }
+        }
+
+        config.count().unwrap_or_else(|err| {
+          eprintln!("Problem counting the prime numbers:\n{err}");
+          process::exit(1);
+        }).primes
+      };
+
+      // Clear the progress line
+      if io::stderr().is_terminal() { eprint!("\r\x1b[K") }
          
       match count.count_start {
         None => {
@@ -334,30 +1105,281 @@ fn main() {
       }
     },
 
+    Arguments::List(list) => {
+      let mut out: io::BufWriter<io::StdoutLock> = io::BufWriter::new(io::stdout().lock());
+
+      let primes: primeutils::PrimeIter = primeutils::try_primes_in_range(list.list_start.unwrap_or(0), list.list_limit).unwrap_or_else(|err| {
+        eprintln!("Problem listing the prime numbers:\n{err}");
+        process::exit(1);
+      });
+
+      for prime in primes {
+        // Stop quietly if stdout is closed (for example, when piped into head)
+        if writeln!(out, "{}", prime).is_err() { return }
+      }
+      let _ = out.flush();
+    },
+
+    Arguments::Merge(merge) => {
+      let mut parts: Vec<primeutils::PartialCount> = Vec::new();
+      for file in &merge.files {
+        let part: primeutils::Result<primeutils::PartialCount> = std::fs::read_to_string(file)
+          .map_err(primeutils::Error::from)
+          .and_then(|text| primeutils::PartialCount::from_text(&text));
+        parts.push(part.unwrap_or_else(|err| {
+          eprintln!("Problem reading the partial count {}:\n{err}", file);
+          process::exit(1);
+        }));
+      }
+
+      let result: primeutils::CountResult = primeutils::merge_counts(&parts).unwrap_or_else(|err| {
+        eprintln!("Problem merging the partial counts:\n{err}");
+        process::exit(1);
+      });
+
+      println!("There are {} prime numbers between {} and {}", result.primes, result.start, result.end);
+    },
+
+    Arguments::Coordinator(coordinator) => {
+      let listener: std::net::TcpListener = std::net::TcpListener::bind(&coordinator.address).unwrap_or_else(|err| {
+        eprintln!("Problem listening on {}:\n{err}", coordinator.address);
+        process::exit(1);
+      });
+      eprintln!("Listening for workers on {}", coordinator.address);
+
+      let options: primeutils::CoordinatorOptions = primeutils::CoordinatorOptions {
+        work_size: coordinator.size,
+        timeout: coordinator.timeout.map(Duration::from_secs),
+        progress: progress_line(),
+      };
+      let result: primeutils::CountResult = primeutils::serve_coordinator(listener, coordinator.limit, coordinator.start, options)
+        .unwrap_or_else(|err| {
+          eprintln!("Problem coordinating the workers:\n{err}");
+          process::exit(1);
+        });
+
+      if io::stderr().is_terminal() { eprint!("\r\x1b[K") }
+      println!("There are {} prime numbers between {} and {}", result.primes, result.start, result.end);
+    },
+
+    Arguments::Worker(worker) => {
+      let ranges: usize = primeutils::run_worker(&worker.address, worker.threads, worker.cache).unwrap_or_else(|err| {
+        eprintln!("Problem working for the coordinator at {}:\n{err}", worker.address);
+        process::exit(1);
+      });
+
+      println!("Counted {} ranges for the coordinator at {}", ranges, worker.address);
+    },
+
+    Arguments::Tune(tune) => {
+      let path: std::path::PathBuf = match tune.profile.map(std::path::PathBuf::from).or_else(primeutils::profile_path) {
+        Some(path) => path,
+        None => {
+          eprintln!("There is no configuration directory to save the profile to, set it with --profile or {}", primeutils::PROFILE_VARIABLE);
+          process::exit(1);
+        },
+      };
+
+      let result: primeutils::TuneResult = primeutils::tune(primeutils::TuneOptions {
+        limit: tune.limit,
+        trial_time: tune.time.map(Duration::from_millis),
+        ..Default::default()
+      }).unwrap_or_else(|err| {
+        eprintln!("Problem timing the counts:\n{err}");
+        process::exit(1);
+      });
+
+      println!("Counted the prime numbers between {} and {}:", result.low, result.high);
+      for trial in &result.trials {
+        println!("  {:>4} threads, {:>6} KiB of cache: {:>8.1} ms", trial.threads, trial.cache >> 10, trial.time.as_secs_f64() * 1000.0);
+      }
+
+      if let Err(err) = result.profile.save(&path) {
+        eprintln!("Problem writing the profile {}:\n{err}", path.display());
+        process::exit(1);
+      }
+      println!("Saved {} threads and {} KiB of cache to {}", result.profile.threads, result.profile.cache >> 10, path.display());
+    },
+
     Arguments::IsPrime(is) => {
-      let is_prime: bool = primeutils::is_prime(is.num);
+      // The 128 bit test falls back to the 64 bit one if num fits in it
+      let is_prime: bool = primeutils::is_prime_u128(is.num);
 
       if is_prime { println!("The number {} is prime", is.num) }
       else { println!("The number {} is not prime", is.num) }
     },
 
+    Arguments::Next(next) => {
+      match primeutils::next_prime_u128(next.num) {
+        Some(prime) => println!("The next prime number after {} is {}", next.num, prime),
+        None => println!("There are no prime numbers after {} that fit in 128 bits", next.num),
+      }
+    },
+
+    Arguments::Prev(prev) => {
+      match primeutils::prev_prime_u128(prev.num) {
+        Some(prime) => println!("The previous prime number before {} is {}", prev.num, prime),
+        None => println!("There are no prime numbers before {}", prev.num),
+      }
+    },
+
     Arguments::Factors(fac) => {
-      let factors: Vec<u64> = primeutils::split_into_factors(fac.num);
+      if fac.num == 0 {
+        println!("The number 0 can't be split into prime factors");
+      }
+      else if fac.list {
+        let factors: Vec<u64> = primeutils::split_into_factors(fac.num);
 
-      println!("The number {} can be split into {:?}", fac.num, factors);
+        println!("The number {} can be split into {:?}", fac.num, factors);
+      }
+      else {
+        let factorization: primeutils::Factorization = primeutils::factorize(fac.num);
+
+        println!("The number {} can be split into {}", fac.num, factorization);
+      }
+    },
+
+    Arguments::Divisors(div) => {
+      let divisors: Vec<u64> = primeutils::divisors(div.num);
+
+      println!("The divisors of {} are {:?}", div.num, divisors);
+    },
+
+    Arguments::DivisorCount(div) => {
+      let divisor_count: u64 = primeutils::divisor_count(div.num);
+
+      println!("The number {} has {} divisors", div.num, divisor_count);
+    },
+
+    Arguments::DivisorSum(div) => {
+      match primeutils::divisor_sum(div.num, div.k) {
+        Some(sum) => println!("The sum of the divisors of {} to the power of {} is {}", div.num, div.k, sum),
+        None => println!("The sum of the divisors of {} to the power of {} doesn't fit in 128 bits", div.num, div.k),
+      }
+    },
+
+    Arguments::Totient(tot) => {
+      let totient: u64 = primeutils::totient(tot.num);
+
+      println!("The totient of {} is {}", tot.num, totient);
+    },
+
+    Arguments::Mobius(mob) => {
+      let mobius: i8 = primeutils::mobius(mob.num);
+
+      println!("The Möbius function of {} is {}", mob.num, mobius);
+    },
+
+    Arguments::Radical(rad) => {
+      let radical: u64 = primeutils::radical(rad.num);
+
+      println!("The radical of {} is {}", rad.num, radical);
     },
 
-    Arguments::GCD(gcd) => {
+    Arguments::Carmichael(car) => {
+      let carmichael: u64 = primeutils::carmichael(car.num);
+
+      println!("The Carmichael function of {} is {}", car.num, carmichael);
+    },
+
+    Arguments::NthPrime(nth) => {
+      match primeutils::nth_prime(nth.num as usize) {
+        Some(prime) => println!("The prime number at index {} is {}", nth.num, prime),
+        None if nth.num == 0 => println!("There is no prime number at index 0, the first one is at index 1"),
+        None => println!("The prime number at index {} is too large", nth.num),
+      }
+    },
+
+    Arguments::PrimePi(pi) => {
+      let primes: usize = primeutils::prime_pi(pi.num as usize);
+
+      println!("There are {} prime numbers less than or equal to {}", primes, pi.num);
+    },
+
+    Arguments::Gcd(gcd) => {
       let greatest_common_divisor: u64 = primeutils::gcd(gcd.x, gcd.y);
 
       println!("The greatest common divisor of {} and {} is {}", gcd.x, gcd.y, greatest_common_divisor);
     },
 
-    Arguments::LCM(lcm) => {
+    Arguments::Lcm(lcm) => {
       let least_common_multiple: u128 = primeutils::lcm(lcm.x, lcm.y);
 
       println!("The least common multiple of {} and {} is {}", lcm.x, lcm.y, least_common_multiple);
     },
+
+    Arguments::TableBuild(build) => {
+      let start: usize = build.table_start.unwrap_or(0);
+      if let Err(err) = primeutils::PrimeTable::build(&build.file, start, build.table_limit) {
+        eprintln!("Problem writing the table {}:\n{err}", build.file);
+        process::exit(1);
+      }
+
+      println!("Saved the prime numbers between {} and {} to {}", start, build.table_limit, build.file);
+    },
+
+    Arguments::TableQuery(query) => {
+      let table: primeutils::PrimeTable = primeutils::PrimeTable::open(&query.file).unwrap_or_else(|err| {
+        eprintln!("Problem reading the table {}:\n{err}", query.file);
+        process::exit(1);
+      });
+
+      match query.query {
+        Query::IsPrime(num) => match table.is_prime(num) {
+          Some(true) => println!("The number {} is prime", num),
+          Some(false) => println!("The number {} is not prime", num),
+          None => println!("The number {} is not in the table, which goes from {} to {}", num, table.low(), table.high()),
+        },
+
+        Query::Count(start, limit) => {
+          let start: usize = start.unwrap_or(table.low());
+          match table.count_primes(start, limit) {
+            Some(primes) => println!("There are {} prime numbers between {} and {}", primes, start, limit),
+            None => println!("The numbers between {} and {} are not in the table, which goes from {} to {}", start, limit, table.low(), table.high()),
+          }
+        },
+
+        Query::List(start, limit) => {
+          let start: usize = start.unwrap_or(table.low());
+          let primes: primeutils::BitSieveIter = table.primes(start, limit).unwrap_or_else(|| {
+            eprintln!("The numbers between {} and {} are not in the table, which goes from {} to {}", start, limit, table.low(), table.high());
+            process::exit(1);
+          });
+
+          let mut out: io::BufWriter<io::StdoutLock> = io::BufWriter::new(io::stdout().lock());
+          for prime in primes {
+            // Stop quietly if stdout is closed (for example, when piped into head)
+            if writeln!(out, "{}", prime).is_err() { return }
+          }
+          let _ = out.flush();
+        },
+
+        Query::Verify => match table.verify() {
+          Ok(()) => println!("The table {} is intact", query.file),
+          Err(err) => {
+            eprintln!("Problem checking the table {}:\n{err}", query.file);
+            process::exit(1);
+          },
+        },
+      }
+    },
+
+    Arguments::CpuInfo() => {
+      let threads: primeutils::ThreadCount = primeutils::get_thread_count();
+      let cache: primeutils::CacheInfo = primeutils::get_cache_info();
+
+      println!("Threads: {} ({})", threads.threads, threads.source);
+      for (name, level) in [("L1 data cache", cache.l1), ("L2 cache", cache.l2), ("L3 cache", cache.l3)] {
+        match level {
+          Some(level) => println!("{}: {} KiB, shared by {} CPU{}", name, level.size >> 10, level.shared_by, if level.shared_by == 1 { "" } else { "s" }),
+          None => println!("{}: unknown", name),
+        }
+      }
+      match primeutils::Profile::for_machine() {
+        Some(profile) => println!("Profile: {} threads, {} KiB of cache", profile.threads, profile.cache >> 10),
+        None => println!("Profile: none (run the tune command to create it)"),
+      }
+    },
   }
 
-}
\ No newline at end of file
+}
@@ -0,0 +1,108 @@
+use crate::is_prime;
+use crate::is_prime_u128;
+use crate::simple_sieve;
+use crate::Error;
+use crate::Result;
+
+// Size in numbers of each window sieved around n
+const WINDOW_SIZE: usize = 1 << 10;
+// The windows are sieved with the primes up to this limit before testing the candidates
+const WINDOW_PRIMES_LIMIT: u32 = 1 << 12;
+
+// Mark in composite the numbers between low and low + composite.len() - 1
+// that are divisible by any of the small primes (without marking the primes)
+fn sieve_window(composite: &mut [bool], small_primes: &[u32], low: u128) {
+
+  composite.fill(false);
+
+  // 0 and 1 aren't prime
+  if low < 2 {
+    for number in low..std::cmp::min(2, low + composite.len() as u128) {
+      composite[(number - low) as usize] = true;
+    }
+  }
+
+  for &prime in small_primes {
+    let prime: u128 = prime as u128;
+
+    // Get the first multiple in the window, skipping the prime itself
+    let mut index: usize = ((prime - low % prime) % prime) as usize;
+    if low < prime * prime {
+      index = (prime * prime - low) as usize;
+    }
+
+    while index < composite.len() {
+      composite[index] = true;
+      index += prime as usize;
+    }
+  }
+}
+
+// Find the lowest prime greater than n and not greater than max
+fn next_prime_below(n: u128, max: u128, test: fn(u128) -> bool) -> Option<u128> {
+
+  let small_primes: Vec<u32> = simple_sieve(WINDOW_PRIMES_LIMIT);
+  let mut composite: Vec<bool> = vec![false; WINDOW_SIZE];
+  let mut low: u128 = n.checked_add(1)?;
+
+  while low <= max {
+    let high: u128 = std::cmp::min(low.saturating_add(WINDOW_SIZE as u128 - 1), max);
+    let size: usize = (high - low) as usize + 1;
+
+    sieve_window(&mut composite[..size], &small_primes, low);
+    for (i, _) in composite.iter().take(size).enumerate().filter(|(_, &is_composite)| !is_composite) {
+      if test(low + i as u128) { return Some(low + i as u128) }
+    }
+
+    if high == max { break }
+    low = high + 1;
+  }
+
+  None
+}
+
+// Find the greatest prime lower than n
+fn prev_prime_above(n: u128, test: fn(u128) -> bool) -> Option<u128> {
+
+  let small_primes: Vec<u32> = simple_sieve(WINDOW_PRIMES_LIMIT);
+  let mut composite: Vec<bool> = vec![false; WINDOW_SIZE];
+  let mut high: u128 = n.checked_sub(1)?;
+
+  loop {
+    let low: u128 = high.saturating_sub(WINDOW_SIZE as u128 - 1);
+    let size: usize = (high - low) as usize + 1;
+
+    sieve_window(&mut composite[..size], &small_primes, low);
+    for (i, _) in composite.iter().take(size).enumerate().rev().filter(|(_, &is_composite)| !is_composite) {
+      if test(low + i as u128) { return Some(low + i as u128) }
+    }
+
+    if low == 0 { return None }
+    high = low - 1;
+  }
+}
+
+// Find the lowest prime greater than n, or None if it doesn't fit in 64 bits
+pub fn next_prime(n: u64) -> Option<u64> {
+  next_prime_below(n as u128, u64::MAX as u128, |num| is_prime(num as u64)).map(|prime| prime as u64)
+}
+
+// Find the lowest prime greater than n like next_prime, or get an error if it doesn't fit in 64 bits
+pub fn try_next_prime(n: u64) -> Result<u64> {
+  next_prime(n).ok_or(Error::Overflow)
+}
+
+// Find the greatest prime lower than n, or None if n is 2 or lower
+pub fn prev_prime(n: u64) -> Option<u64> {
+  prev_prime_above(n as u128, |num| is_prime(num as u64)).map(|prime| prime as u64)
+}
+
+// Find the lowest prime greater than n, or None if it doesn't fit in 128 bits
+pub fn next_prime_u128(n: u128) -> Option<u128> {
+  next_prime_below(n, u128::MAX, is_prime_u128)
+}
+
+// Find the greatest prime lower than n, or None if n is 2 or lower
+pub fn prev_prime_u128(n: u128) -> Option<u128> {
+  prev_prime_above(n, is_prime_u128)
+}
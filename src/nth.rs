@@ -0,0 +1,136 @@
+use crate::lmo::count_primes_lmo;
+use crate::mobius;
+use crate::segment_primes;
+use crate::segment_sieve;
+use crate::simple_sieve;
+use crate::wheel;
+use crate::Error;
+use crate::Result;
+
+// Below this index, the primes are found with a single sieve
+const SIEVE_LIMIT: usize = 1 << 16;
+// Size in numbers of each segment sieved around the estimate
+const SEGMENT_SIZE: usize = 1 << 18;
+
+// Logarithmic integral, li(x), using Ramanujan's series
+fn li(x: f64) -> f64 {
+
+  // Euler-Mascheroni constant
+  const GAMMA: f64 = 0.577_215_664_901_532_9;
+
+  let ln: f64 = x.ln();
+  let mut sum: f64 = 0.0;
+  let mut inner: f64 = 0.0;
+  let mut factor: f64 = 1.0;
+
+  for n in 1..200 {
+    // factor = (-1)^(n-1) * ln(x)^n / (n! * 2^(n-1))
+    factor *= if n == 1 { ln } else { -ln / (2.0 * n as f64) };
+    if n % 2 == 1 { inner += 1.0 / n as f64 }
+
+    let term: f64 = factor * inner;
+    sum += term;
+    if term.abs() < 1e-17 * sum.abs() { break }
+  }
+
+  GAMMA + ln.ln() + x.sqrt() * sum
+}
+
+// Riemann's prime counting function, R(x) = Σ μ(k) / k * li(x^(1/k))
+fn riemann_r(x: f64) -> f64 {
+
+  let mut sum: f64 = 0.0;
+  let mut k: u64 = 1;
+
+  loop {
+    let root: f64 = x.powf(1.0 / k as f64);
+    if root < 2.0 { break }
+
+    let mu: i8 = mobius(k);
+    if mu != 0 { sum += mu as f64 / k as f64 * li(root) }
+    k += 1;
+  }
+
+  sum
+}
+
+// Approximate the n-th prime inverting R(x) with Newton's method
+fn inverse_riemann_r(n: f64) -> f64 {
+
+  // The derivative of R(x) is close to 1 / ln(x)
+  let mut x: f64 = n * n.ln();
+  for _ in 0..100 {
+    let step: f64 = (riemann_r(x) - n) * x.ln();
+    x -= step;
+    if step.abs() < 0.5 { break }
+  }
+
+  x
+}
+
+// Find the n-th prime (starting at 1 for 2), or None if n is 0 or it doesn't fit in usize.
+// The number of primes up to an estimate of the n-th prime is counted, and then the gap
+// between the estimate and the prime is sieved forwards or backwards
+pub fn nth_prime(n: usize) -> Option<usize> {
+
+  if n == 0 { return None }
+
+  // The n-th prime is lower than n * (ln(n) + ln(ln(n))) for n >= 6
+  if n < SIEVE_LIMIT {
+    let bound: f64 = if n < 6 { 13.0 } else { n as f64 * ((n as f64).ln() + (n as f64).ln().ln()) };
+    return simple_sieve(bound as u32).get(n - 1).map(|&prime| prime as usize);
+  }
+
+  let estimate: f64 = inverse_riemann_r(n as f64);
+  if estimate >= usize::MAX as f64 { return None }
+  let estimate: usize = estimate as usize;
+  let mut count: usize = count_primes_lmo(estimate);
+
+  // The gap is much lower than the estimate, so the primes up to
+  // the square root of twice the estimate are enough to sieve it
+  let sqrt: u32 = std::cmp::min(estimate.saturating_mul(2).isqrt(), u32::MAX as usize) as u32;
+  let small_primes: Vec<u32> = simple_sieve(sqrt);
+  let mut sieve: Vec<u64> = vec![u64::MAX; wheel::words::<u64>(SEGMENT_SIZE / wheel::WHEEL + 2)];
+  let mut found: Vec<usize> = Vec::new();
+
+  if count >= n {
+    // Sieve backwards from the estimate until the segment containing the prime
+    let mut high: usize = estimate;
+    loop {
+      let low: usize = high.saturating_sub(SEGMENT_SIZE - 1).max(2);
+      let segment_count: usize = segment_sieve(&mut sieve, &small_primes, low, high) as usize;
+
+      if count - segment_count < n {
+        segment_primes(&mut sieve, &small_primes, low, high, &mut found);
+        return Some(found[n - (count - segment_count) - 1]);
+      }
+
+      if low == 2 { return None }
+      count -= segment_count;
+      high = low - 1;
+    }
+  }
+  else {
+    // Sieve forwards from the estimate until the segment containing the prime
+    let mut low: usize = estimate + 1;
+    loop {
+      let high: usize = low.saturating_add(SEGMENT_SIZE - 1);
+      let segment_count: usize = segment_sieve(&mut sieve, &small_primes, low, high) as usize;
+
+      if count + segment_count >= n {
+        segment_primes(&mut sieve, &small_primes, low, high, &mut found);
+        return Some(found[n - count - 1]);
+      }
+
+      if high == usize::MAX { return None }
+      count += segment_count;
+      low = high + 1;
+    }
+  }
+}
+
+// Find the n-th prime like nth_prime, or get an error if n is 0 or the prime doesn't fit in usize
+pub fn try_nth_prime(n: usize) -> Result<usize> {
+  if n == 0 { return Err(Error::Zero) }
+  nth_prime(n).ok_or(Error::Overflow)
+}
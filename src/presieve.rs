@@ -0,0 +1,46 @@
+use std::sync::OnceLock;
+
+use crate::bits::Word;
+use crate::wheel;
+
+// Primes whose multiples are crossed off by copying a pattern instead of sieving them
+pub const PRESIEVE_PRIMES: [u32; 4] = [7, 11, 13, 17];
+
+// The wheel bytes repeat every 7 * 11 * 13 * 17 bytes, as 30 is coprime to them
+const PATTERN_SIZE: usize = 7 * 11 * 13 * 17;
+
+static PATTERN: OnceLock<Vec<u8>> = OnceLock::new();
+
+// Get the bytes of a sieve starting at 0 with every multiple of the presieve primes crossed off,
+// followed by its first 7 bytes again so a word can be read from any byte of the pattern
+fn pattern() -> &'static [u8] {
+  PATTERN.get_or_init(|| {
+    let mut pattern: Vec<u8> = vec![0xff; PATTERN_SIZE];
+    for prime in PRESIEVE_PRIMES {
+      wheel::cross_off(&mut pattern, 0, PATTERN_SIZE * wheel::WHEEL - 1, prime as usize, 1);
+    }
+    pattern.extend_from_within(..7);
+    pattern
+  })
+}
+
+// Check if a list of sieving primes contains all the presieve primes
+pub fn covers(primes: &[u32]) -> bool {
+  PRESIEVE_PRIMES.iter().all(|prime| primes.binary_search(prime).is_ok())
+}
+
+// Fill a sieve starting at base (a multiple of 30) with the pattern,
+// so only the primes greater than the presieve ones are left to cross off
+pub fn fill<W: Word>(sieve: &mut [W], base: usize) {
+
+  let pattern: &[u8] = pattern();
+  let per_word: usize = wheel::bytes_per_word::<W>();
+  let mut offset: usize = (base / wheel::WHEEL) % PATTERN_SIZE;
+
+  // Copy the pattern as many times as needed, starting at the offset of base
+  for word in sieve.iter_mut() {
+    *word = W::from_bytes(&pattern[offset..offset + per_word]);
+    offset += per_word;
+    if offset >= PATTERN_SIZE { offset -= PATTERN_SIZE }
+  }
+}
@@ -0,0 +1,232 @@
+// The first twelve primes. Used both as a trial division pre-filter
+// and as the Miller-Rabin witnesses, which are enough to make the test
+// deterministic for every number below 3.3 * 10^24 (so the whole u64 range)
+pub const SMALL_PRIMES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+// Multiply a and b modulo m, using 128 bits to avoid overflowing
+#[inline]
+pub fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+  ((a as u128 * b as u128) % m as u128) as u64
+}
+
+// Raise base to the power of exp modulo m
+pub fn pow_mod(base: u64, exp: u64, m: u64) -> u64 {
+
+  let mut result: u64 = 1 % m;
+  let mut base: u64 = base % m;
+  let mut exp: u64 = exp;
+
+  while exp > 0 {
+    if exp & 1 == 1 {
+      result = mul_mod(result, base, m);
+    }
+    base = mul_mod(base, base, m);
+    exp >>= 1;
+  }
+
+  result
+}
+
+// Check if the odd number n (greater than 2) is a strong probable prime to base a,
+// where n - 1 = d * 2^s with d odd
+fn is_strong_probable_prime(n: u64, d: u64, s: u32, a: u64) -> bool {
+
+  let mut x: u64 = pow_mod(a, d, n);
+  if x == 1 || x == n - 1 { return true }
+
+  // Square x up to s - 1 times looking for n - 1
+  for _ in 1..s {
+    x = mul_mod(x, x, n);
+    if x == n - 1 { return true }
+    if x == 1 { return false }
+  }
+
+  false
+}
+
+// Deterministic Miller-Rabin test for odd numbers greater than the witnesses
+pub fn miller_rabin(n: u64) -> bool {
+
+  // Write n - 1 as d * 2^s
+  let s: u32 = (n - 1).trailing_zeros();
+  let d: u64 = (n - 1) >> s;
+
+  SMALL_PRIMES.iter().all(|&a| is_strong_probable_prime(n, d, s, a))
+}
+
+// Add a and b (both lower than m) modulo m without overflowing
+#[inline]
+fn add_mod_u128(a: u128, b: u128, m: u128) -> u128 {
+  if a >= m - b { a - (m - b) } else { a + b }
+}
+
+// Subtract b from a (both lower than m) modulo m
+#[inline]
+fn sub_mod_u128(a: u128, b: u128, m: u128) -> u128 {
+  if a >= b { a - b } else { m - (b - a) }
+}
+
+// Divide a (lower than m) by 2 modulo the odd number m
+#[inline]
+fn half_mod_u128(a: u128, m: u128) -> u128 {
+  // If a is odd, a + m is even, so compute (a + m) / 2 without overflowing
+  if a & 1 == 1 { (a >> 1) + (m >> 1) + 1 } else { a >> 1 }
+}
+
+// Multiply a and b modulo m without overflowing 128 bits
+pub fn mul_mod_u128(a: u128, b: u128, m: u128) -> u128 {
+
+  // If the modulus fits in 64 bits, the product fits in 128 bits
+  if m <= u64::MAX as u128 {
+    return (a % m) * (b % m) % m;
+  }
+
+  // Else multiply by doubling and adding
+  let mut result: u128 = 0;
+  let mut a: u128 = a % m;
+  let mut b: u128 = b % m;
+
+  while b > 0 {
+    if b & 1 == 1 {
+      result = add_mod_u128(result, a, m);
+    }
+    a = add_mod_u128(a, a, m);
+    b >>= 1;
+  }
+
+  result
+}
+
+// Raise base to the power of exp modulo m without overflowing 128 bits
+pub fn pow_mod_u128(base: u128, exp: u128, m: u128) -> u128 {
+
+  let mut result: u128 = 1 % m;
+  let mut base: u128 = base % m;
+  let mut exp: u128 = exp;
+
+  while exp > 0 {
+    if exp & 1 == 1 {
+      result = mul_mod_u128(result, base, m);
+    }
+    base = mul_mod_u128(base, base, m);
+    exp >>= 1;
+  }
+
+  result
+}
+
+// Check if the odd number n (greater than 2) is a strong probable prime to base 2
+fn is_strong_probable_prime_base_2(n: u128) -> bool {
+
+  // Write n - 1 as d * 2^s
+  let s: u32 = (n - 1).trailing_zeros();
+  let d: u128 = (n - 1) >> s;
+
+  let mut x: u128 = pow_mod_u128(2, d, n);
+  if x == 1 || x == n - 1 { return true }
+
+  for _ in 1..s {
+    x = mul_mod_u128(x, x, n);
+    if x == n - 1 { return true }
+    if x == 1 { return false }
+  }
+
+  false
+}
+
+// Compute the Jacobi symbol (a/n) for an odd n
+fn jacobi(a: u128, n: u128) -> i32 {
+
+  let mut a: u128 = a % n;
+  let mut n: u128 = n;
+  let mut result: i32 = 1;
+
+  while a != 0 {
+    // Pull out the factors of two, which flip the sign when n is 3 or 5 mod 8
+    while a & 1 == 0 {
+      a >>= 1;
+      if n % 8 == 3 || n % 8 == 5 { result = -result }
+    }
+
+    // Quadratic reciprocity
+    (a, n) = (n, a);
+    if a % 4 == 3 && n % 4 == 3 { result = -result }
+    a %= n;
+  }
+
+  if n == 1 { result } else { 0 }
+}
+
+// Strong Lucas probable prime test with the Selfridge parameters,
+// for an odd n greater than 2 that is not a perfect square
+fn is_strong_lucas_probable_prime(n: u128) -> bool {
+
+  // Find the first D in 5, -7, 9, -11, ... such that (D/n) = -1
+  let mut d_abs: u128 = 5;
+  let mut d_negative: bool = false;
+  loop {
+    let d_mod: u128 = if d_negative { sub_mod_u128(0, d_abs % n, n) } else { d_abs % n };
+
+    match jacobi(d_mod, n) {
+      -1 => break,
+      // D shares a factor with n, so n is composite unless it is that factor
+      0 if d_abs != n => return false,
+      _ => {},
+    }
+
+    d_abs += 2;
+    d_negative = !d_negative;
+  }
+
+  // P = 1 and Q = (1 - D) / 4, all of them modulo n
+  let d: u128 = if d_negative { sub_mod_u128(0, d_abs % n, n) } else { d_abs % n };
+  let q: u128 = if d_negative { ((d_abs + 1) / 4) % n } else { sub_mod_u128(0, ((d_abs - 1) / 4) % n, n) };
+
+  // Write n + 1 as k * 2^s, with k odd
+  let s: u32 = (n + 1).trailing_zeros();
+  let k: u128 = (n + 1) >> s;
+
+  // Compute U_k, V_k and Q^k from the most significant bit of k
+  let mut u: u128 = 1;
+  let mut v: u128 = 1;
+  let mut q_k: u128 = q;
+
+  for bit in (0..(127 - k.leading_zeros())).rev() {
+    // Double the index
+    u = mul_mod_u128(u, v, n);
+    v = sub_mod_u128(mul_mod_u128(v, v, n), add_mod_u128(q_k, q_k, n), n);
+    q_k = mul_mod_u128(q_k, q_k, n);
+
+    // Add one to the index if the bit is set
+    if (k >> bit) & 1 == 1 {
+      let next_u: u128 = half_mod_u128(add_mod_u128(u, v, n), n);
+      v = half_mod_u128(add_mod_u128(mul_mod_u128(d, u, n), v, n), n);
+      u = next_u;
+      q_k = mul_mod_u128(q_k, q, n);
+    }
+  }
+
+  if u == 0 || v == 0 { return true }
+
+  // Check V_(k * 2^r) for every r lower than s
+  for _ in 1..s {
+    v = sub_mod_u128(mul_mod_u128(v, v, n), add_mod_u128(q_k, q_k, n), n);
+    if v == 0 { return true }
+    q_k = mul_mod_u128(q_k, q_k, n);
+  }
+
+  false
+}
+
+// Baillie-PSW test for odd numbers greater than the small primes.
+// It has no known counterexamples, and none exist below 2^64
+pub fn baillie_psw(n: u128) -> bool {
+
+  if !is_strong_probable_prime_base_2(n) { return false }
+
+  // The Lucas test needs n not to be a perfect square
+  let root: u128 = n.isqrt();
+  if root * root == n { return false }
+
+  is_strong_lucas_probable_prime(n)
+}
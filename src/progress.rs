@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Token to stop a count from another thread. The threads of the count check it between
+// segments, so it stops soon after it is cancelled. Clones share the same state
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+  cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+
+  pub fn new() -> CancelToken {
+    CancelToken { cancelled: Arc::new(AtomicBool::new(false)) }
+  }
+
+  // Ask the counts using this token to stop
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed)
+  }
+}
+
+// Progress of a count, passed to the progress callback every time a chunk of it is sieved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+  // Chunks sieved (including the ones of a resumed checkpoint) and chunks in the whole count
+  pub done: usize,
+  pub total: usize,
+  // Primes found in the chunks sieved
+  pub primes: usize,
+  pub elapsed: Duration,
+}
+
+// Callback that receives the progress of a count
+pub type ProgressCallback = Box<dyn FnMut(&Progress)>;
+
+// Options of count_primes_with. Threads and cache are taken from the profile saved by tune, or
+// chosen for the machine, if not set
+#[derive(Default)]
+pub struct CountOptions {
+  pub threads: Option<usize>,
+  // Bytes of L1 for each thread, the segments are sized for L2 (or this if it is larger)
+  pub cache: Option<usize>,
+  // Called from the thread that started the count
+  pub progress: Option<ProgressCallback>,
+  pub cancel: Option<CancelToken>,
+}
+
+// Result of a count, which may cover only part of the range if it was cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountResult {
+  // How many primes there are between start and end (both included). If the
+  // count was cancelled before covering any number, end is lower than start
+  pub primes: usize,
+  pub start: usize,
+  pub end: usize,
+  pub cancelled: bool,
+}
@@ -0,0 +1,158 @@
+use std::cmp::Ordering;
+
+use crate::error::{Error, Result};
+use crate::progress::CountResult;
+
+// First line of every partial count
+const HEADER: &str = "primeutils partial count 1";
+
+// Part of a count to do in one process, so a count can be split across processes or machines.
+// The range is split in count parts of the same size (give or take one number), and index
+// (starting at 1) chooses one of them, so every process gets the same parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+  index: usize,
+  count: usize,
+}
+
+impl Shard {
+
+  // Get the shard index of count, or None if index is not between 1 and count
+  pub fn new(index: usize, count: usize) -> Option<Shard> {
+    if index == 0 || index > count { return None }
+    Some(Shard { index, count })
+  }
+
+  pub fn index(&self) -> usize {
+    self.index
+  }
+
+  pub fn count(&self) -> usize {
+    self.count
+  }
+
+  // Get the first and the last numbers of the shard in the range between start and limit,
+  // or None if the range is empty or there are more shards than numbers in it
+  pub fn range(&self, start: usize, limit: usize) -> Option<(usize, usize)> {
+    if start > limit { return None }
+    let numbers: u128 = (limit - start) as u128 + 1;
+    if self.count as u128 > numbers { return None }
+
+    let first = |index: usize| start + (numbers * index as u128 / self.count as u128) as usize;
+    // The last number of a shard is one below the first of the next one, which may not fit for the last shard
+    Some((first(self.index - 1), if self.index == self.count { limit } else { first(self.index) - 1 }))
+  }
+}
+
+// Result of counting a shard of the primes between start and limit: how many
+// primes there are between low and high, and the segment size used to sieve them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialCount {
+  pub shard: Shard,
+  pub start: usize,
+  pub limit: usize,
+  pub low: usize,
+  pub high: usize,
+  pub primes: usize,
+  pub segment_size: usize,
+}
+
+impl PartialCount {
+
+  // Write the partial count as text, with a value per line
+  pub fn to_text(&self) -> String {
+    format!(
+      "{HEADER}\nshard {}/{}\nstart {}\nlimit {}\nrange {}..{}\nprimes {}\nsegment {}\n",
+      self.shard.index, self.shard.count, self.start, self.limit, self.low, self.high, self.primes, self.segment_size
+    )
+  }
+
+  // Read a partial count written with to_text
+  pub fn from_text(text: &str) -> Result<PartialCount> {
+
+    let invalid = || Error::InvalidData(String::from("invalid partial count"));
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) { return Err(invalid()) }
+
+    // Read the value of a line, checking its name
+    let mut value = |name: &str| -> Result<&str> {
+      match lines.next().and_then(|line| line.split_once(' ')) {
+        Some((key, value)) if key == name => Ok(value),
+        _ => Err(invalid()),
+      }
+    };
+    let number = |value: &str| value.parse::<usize>().map_err(|_| invalid());
+    let pair = |value: &str, separator: &str| -> Result<(usize, usize)> {
+      let (first, second): (&str, &str) = value.split_once(separator).ok_or_else(invalid)?;
+      Ok((number(first)?, number(second)?))
+    };
+
+    let (index, count): (usize, usize) = pair(value("shard")?, "/")?;
+    let shard: Shard = Shard::new(index, count).ok_or_else(invalid)?;
+    let start: usize = number(value("start")?)?;
+    let limit: usize = number(value("limit")?)?;
+    let (low, high): (usize, usize) = pair(value("range")?, "..")?;
+    let primes: usize = number(value("primes")?)?;
+    let segment_size: usize = number(value("segment")?)?;
+
+    // The range must be inside the count (it is empty if the shard was cancelled before counting any number)
+    if low < start || high > limit || high.saturating_add(1) < low { return Err(invalid()) }
+
+    Ok(PartialCount { shard, start, limit, low, high, primes, segment_size })
+  }
+}
+
+// Add the primes of the partial counts of every shard of a count, checking that they are of
+// the same count, sieved with the same segment size, and that each one covers the range of its
+// shard. Returns Error::ShardMismatch saying which part doesn't if any
+pub fn merge_counts(parts: &[PartialCount]) -> Result<CountResult> {
+
+  let mismatch = Error::ShardMismatch;
+  let first: &PartialCount = parts.first().ok_or_else(|| mismatch(String::from("there are no partial counts to merge")))?;
+  let (start, limit, shards): (usize, usize, usize) = (first.start, first.limit, first.shard.count);
+
+  if let Some(part) = parts.iter().find(|part| part.start != start || part.limit != limit || part.shard.count != shards) {
+    return Err(mismatch(format!(
+      "shard {}/{} is of the count between {} and {}, not between {} and {} in {} shards",
+      part.shard.index, part.shard.count, part.start, part.limit, start, limit, shards
+    )));
+  }
+  if let Some(part) = parts.iter().find(|part| part.segment_size != first.segment_size) {
+    return Err(mismatch(format!(
+      "shard {}/{} was sieved with segments of {} numbers, not {}",
+      part.shard.index, shards, part.segment_size, first.segment_size
+    )));
+  }
+
+  // Every shard must be there once
+  let mut sorted: Vec<&PartialCount> = parts.iter().collect();
+  sorted.sort_by_key(|part| part.shard.index);
+  for (i, part) in sorted.iter().enumerate() {
+    match part.shard.index.cmp(&(i + 1)) {
+      Ordering::Less => return Err(mismatch(format!("shard {}/{} is repeated", part.shard.index, shards))),
+      Ordering::Greater => return Err(mismatch(format!("shard {}/{} is missing", i + 1, shards))),
+      Ordering::Equal => {},
+    }
+  }
+  if sorted.len() < shards {
+    return Err(mismatch(format!("shard {}/{} is missing", sorted.len() + 1, shards)));
+  }
+
+  // And each one must have counted the range of its shard, so they cover the count from start to limit
+  for part in &sorted {
+    let (low, high): (usize, usize) = part.shard.range(start, limit).ok_or(Error::TooManyShards { start, limit, shards })?;
+    if (part.low, part.high) != (low, high) {
+      return Err(mismatch(format!(
+        "shard {}/{} counted the numbers between {} and {} instead of between {} and {}",
+        part.shard.index, shards, part.low, part.high, low, high
+      )));
+    }
+  }
+
+  Ok(CountResult {
+    primes: sorted.iter().map(|part| part.primes).sum(),
+    start,
+    end: limit,
+    cancelled: false,
+  })
+}
@@ -0,0 +1,130 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::bitsieve::{BitSieve, BitSieveIter};
+use crate::error::{Error, Result};
+
+// Every table file starts with these bytes
+const MAGIC: [u8; 8] = *b"PRIMETBL";
+const VERSION: u32 = 1;
+
+// Layouts of the bitmap. Only the mod 30 wheel of BitSieve is written, the
+// odd numbers layout is reserved so older tables can be told apart
+const LAYOUT_ODD: u32 = 0;
+const LAYOUT_WHEEL: u32 = 1;
+
+// Bytes of the header of a table: magic, version, layout and checksum of the payload
+const HEADER_SIZE: usize = 8 + 4 + 4 + 8;
+
+// The prime numbers of a range stored in a file, so they can be queried in later runs
+// without sieving them again. The file has a header (magic, version, layout and a
+// FNV-1a checksum, every number in little endian) followed by a serialized BitSieve,
+// which is read in place through a memory map
+pub struct PrimeTable {
+  sieve: BitSieve,
+  // Checksum of the payload written in the header
+  checksum: u64,
+}
+
+// 64 bit FNV-1a hash of some bytes
+fn checksum(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf29ce484222325, |hash: u64, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn invalid_data(message: &str) -> Error {
+  Error::InvalidData(String::from(message))
+}
+
+impl PrimeTable {
+
+  // Write the prime numbers set in a sieve to a table file
+  pub fn write<P: AsRef<Path>>(path: P, sieve: &BitSieve) -> Result<()> {
+
+    let payload: Vec<u8> = sieve.to_bytes();
+
+    let mut file: io::BufWriter<File> = io::BufWriter::new(File::create(path)?);
+    file.write_all(&MAGIC)?;
+    file.write_all(&VERSION.to_le_bytes())?;
+    file.write_all(&LAYOUT_WHEEL.to_le_bytes())?;
+    file.write_all(&checksum(&payload).to_le_bytes())?;
+    file.write_all(&payload)?;
+    Ok(file.flush()?)
+  }
+
+  // Sieve the numbers between low and high and write them to a table file, or get
+  // Error::OutOfMemory if there isn't memory to sieve them
+  pub fn build<P: AsRef<Path>>(path: P, low: usize, high: usize) -> Result<()> {
+    PrimeTable::write(path, &BitSieve::try_new(low, high)?)
+  }
+
+  // Open a table file, checking its header and that the sizes in it match the file. The
+  // payload isn't read, so its checksum is only checked by verify
+  pub fn open<P: AsRef<Path>>(path: P) -> Result<PrimeTable> {
+
+    let file: File = File::open(path)?;
+    // The map is only read, and the file is assumed not to be modified while it is open
+    let map: Mmap = unsafe { Mmap::map(&file)? };
+
+    if map.len() < HEADER_SIZE || map[..8] != MAGIC { return Err(invalid_data("not a prime table")) }
+    let read_u32 = |position: usize| u32::from_le_bytes(map[position..position + 4].try_into().unwrap());
+
+    if read_u32(8) != VERSION { return Err(invalid_data("unsupported prime table version")) }
+    match read_u32(12) {
+      LAYOUT_WHEEL => {},
+      LAYOUT_ODD => return Err(invalid_data("the odd numbers layout is not supported")),
+      _ => return Err(invalid_data("unknown prime table layout")),
+    }
+
+    let checksum: u64 = u64::from_le_bytes(map[16..24].try_into().unwrap());
+    let sieve: BitSieve = BitSieve::from_map(map, HEADER_SIZE).ok_or_else(|| invalid_data("invalid prime table bitmap"))?;
+    Ok(PrimeTable { sieve, checksum })
+  }
+
+  // Check the checksum of the payload, reading the whole file. It only detects accidental
+  // corruption, as anyone can compute it for a different payload
+  pub fn verify(&self) -> Result<()> {
+    // The sieve is never modified, so it is still read from the map
+    let map: &Mmap = self.sieve.map().expect("the sieve of a table is mapped");
+    if checksum(&map[HEADER_SIZE..]) != self.checksum { return Err(invalid_data("prime table checksum mismatch")) }
+    Ok(())
+  }
+
+  // Lowest number of the table
+  pub fn low(&self) -> usize {
+    self.sieve.low()
+  }
+
+  // Greatest number of the table
+  pub fn high(&self) -> usize {
+    self.sieve.high()
+  }
+
+  // Check if a number is prime, or None if it is not in the table
+  pub fn is_prime(&self, num: usize) -> Option<bool> {
+    self.sieve.get(num)
+  }
+
+  // Count the prime numbers between start and limit (both included),
+  // or None if they are not all in the table
+  pub fn count_primes(&self, start: usize, limit: usize) -> Option<usize> {
+    if start < self.low() || limit > self.high() { return None }
+    if start > limit { return Some(0) }
+    Some(self.sieve.rank(limit) - start.checked_sub(1).map_or(0, |below| self.sieve.rank(below)))
+  }
+
+  // Iterate over the prime numbers between start and end (both included),
+  // or None if they are not all in the table
+  pub fn primes(&self, start: usize, end: usize) -> Option<BitSieveIter<'_>> {
+    if start < self.low() || end > self.high() { return None }
+    Some(self.sieve.range(start, end))
+  }
+
+  // Get the sieve read from the table
+  pub fn sieve(&self) -> &BitSieve {
+    &self.sieve
+  }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::cpu;
+use crate::error::{Error, Result};
+use crate::progress::CountOptions;
+
+// First line of every profile file
+const HEADER: &str = "primeutils tune profile 1";
+// Environment variable with the path of the profile, instead of the one in the configuration directory
+pub const PROFILE_VARIABLE: &str = "PRIMEUTILS_PROFILE";
+
+// Numbers the trials are sieved from if no limit is given
+const TUNE_LIMIT: usize = 1_000_000_000_000;
+// Time each trial should take
+const TRIAL_TIME: Duration = Duration::from_millis(100);
+// Times each trial is run, keeping the fastest one
+const TRIAL_RUNS: usize = 2;
+// Smallest cache size tried, and width of the first range timed to calibrate the trials
+const MIN_CACHE: usize = 1 << 13;
+const CALIBRATION_NUMBERS: usize = 10_000_000;
+
+// Threads and cache (the L1 size of CountOptions) that sieve the fastest on a machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+  // Name of the machine that was tuned, so a profile in a shared directory isn't used on another one
+  pub machine: String,
+  pub threads: usize,
+  pub cache: usize,
+}
+
+impl Profile {
+
+  // Write the profile to a file, as text with a value per line, creating its directory
+  pub fn save(&self, path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+      fs::create_dir_all(dir)?;
+    }
+    Ok(fs::write(path, format!("{HEADER}\nmachine {}\nthreads {}\ncache {}\n", self.machine, self.threads, self.cache))?)
+  }
+
+  // Read a profile written with save
+  pub fn load(path: &Path) -> Result<Profile> {
+
+    let invalid = || Error::InvalidData(String::from("invalid tune profile"));
+    let text: String = fs::read_to_string(path)?;
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) { return Err(invalid()) }
+
+    // Read the value of a line, checking its name
+    let mut value = |name: &str| -> Result<&str> {
+      match lines.next().and_then(|line| line.split_once(' ')) {
+        Some((key, value)) if key == name => Ok(value),
+        _ => Err(invalid()),
+      }
+    };
+    let number = |value: &str| value.parse::<usize>().ok().filter(|&value| value > 0).ok_or_else(invalid);
+
+    let machine: String = String::from(value("machine")?);
+    let threads: usize = number(value("threads")?)?;
+    let cache: usize = number(value("cache")?)?;
+    Ok(Profile { machine, threads, cache })
+  }
+
+  // Get the profile of this machine from profile_path, or None if there isn't one
+  // (or it is invalid, or of another machine)
+  pub fn for_machine() -> Option<Profile> {
+    Profile::load(&profile_path()?).ok().filter(|profile| profile.machine == machine_name())
+  }
+}
+
+// Get the path of the profile: PRIMEUTILS_PROFILE if it is set, else primeutils/profile in the
+// configuration directory ($XDG_CONFIG_HOME, or ~/.config), or None if there is none
+pub fn profile_path() -> Option<PathBuf> {
+  if let Some(path) = std::env::var_os(PROFILE_VARIABLE).filter(|path| !path.is_empty()) {
+    return Some(PathBuf::from(path));
+  }
+  let config: PathBuf = match std::env::var_os("XDG_CONFIG_HOME").filter(|path| !path.is_empty()) {
+    Some(dir) => PathBuf::from(dir),
+    None => PathBuf::from(std::env::var_os("HOME").filter(|path| !path.is_empty())?).join(".config"),
+  };
+  Some(config.join("primeutils").join("profile"))
+}
+
+// Get the host name of the machine, or "unknown" if it can't be found
+pub fn machine_name() -> String {
+  ["/proc/sys/kernel/hostname", "/etc/hostname"].iter()
+    .filter_map(|path| fs::read_to_string(path).ok())
+    .map(|name| String::from(name.trim()))
+    .chain(std::env::var("HOSTNAME").ok())
+    .find(|name| !name.is_empty() && !name.contains(char::is_whitespace))
+    .unwrap_or_else(|| String::from("unknown"))
+}
+
+// Options of tune. The grid and the limit are chosen for the machine if not set
+#[derive(Debug, Clone, Default)]
+pub struct TuneOptions {
+  // The trials count the primes in a range above this
+  pub limit: Option<usize>,
+  // Time each trial should take, TRIAL_TIME by default
+  pub trial_time: Option<Duration>,
+  // Cache sizes and threads to try, every combination of them
+  pub caches: Option<Vec<usize>>,
+  pub threads: Option<Vec<usize>>,
+}
+
+// Time of the fastest run of counting the range of the trials with some threads and cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trial {
+  pub threads: usize,
+  pub cache: usize,
+  pub time: Duration,
+}
+
+// Result of tune: the fastest profile, and every trial in the order they were run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TuneResult {
+  pub profile: Profile,
+  // Range counted by each trial (both ends included)
+  pub low: usize,
+  pub high: usize,
+  pub trials: Vec<Trial>,
+}
+
+// Get the powers of two from MIN_CACHE up to the L2 size (at least 4 of them), and the powers
+// of two up to the threads of the machine with the threads themselves
+fn default_grid() -> (Vec<usize>, Vec<usize>) {
+  let largest: usize = std::cmp::max(cpu::get_cache_info().l2_size(), MIN_CACHE << 3);
+  let caches: Vec<usize> = (MIN_CACHE.ilog2()..=largest.ilog2()).map(|shift| 1 << shift).collect();
+
+  let cores: usize = cpu::get_cores();
+  let mut threads: Vec<usize> = (0..=cores.ilog2()).map(|shift| 1 << shift).collect();
+  if !threads.contains(&cores) { threads.push(cores) }
+  (caches, threads)
+}
+
+// Time the fastest of TRIAL_RUNS counts of the primes between low and high
+fn time_count(low: usize, high: usize, threads: usize, cache: usize) -> Result<Duration> {
+  let mut fastest: Duration = Duration::MAX;
+  for _ in 0..TRIAL_RUNS {
+    let now: Instant = Instant::now();
+    crate::count_primes_with(high, Some(low), CountOptions { threads: Some(threads), cache: Some(cache), ..Default::default() })?;
+    fastest = fastest.min(now.elapsed());
+  }
+  Ok(fastest)
+}
+
+// Find the threads and cache that count primes the fastest on this machine. The range of the
+// trials is calibrated first, so one takes about the trial time with the threads and cache
+// chosen without a profile, and then it is counted with every combination of the grid.
+// Returns an error if any count fails
+pub fn tune(options: TuneOptions) -> Result<TuneResult> {
+
+  let limit: usize = options.limit.unwrap_or(TUNE_LIMIT).max(2);
+  let trial_time: Duration = options.trial_time.unwrap_or(TRIAL_TIME).max(Duration::from_millis(1));
+  let (default_caches, default_threads): (Vec<usize>, Vec<usize>) = default_grid();
+  let caches: Vec<usize> = options.caches.filter(|caches| !caches.is_empty()).unwrap_or(default_caches);
+  let threads: Vec<usize> = options.threads.filter(|threads| !threads.is_empty()).unwrap_or(default_threads);
+
+  // Grow or shrink the range until it takes between half and twice the trial time, a few times at most
+  let mut numbers: usize = CALIBRATION_NUMBERS;
+  for _ in 0..8 {
+    let high: usize = limit.saturating_add(numbers - 1);
+    let time: Duration = time_count(limit, high, cpu::get_cores(), cpu::get_cache_size())?.max(Duration::from_micros(1));
+    if (time >= trial_time / 2 && time <= trial_time * 2) || high == usize::MAX { break }
+    let scale: f64 = (trial_time.as_secs_f64() / time.as_secs_f64()).clamp(1.0 / 16.0, 16.0);
+    numbers = std::cmp::max((numbers as f64 * scale) as usize, 1);
+  }
+  let (low, high): (usize, usize) = (limit, limit.saturating_add(numbers - 1));
+
+  let mut trials: Vec<Trial> = Vec::new();
+  for &threads in &threads {
+    for &cache in &caches {
+      trials.push(Trial { threads, cache, time: time_count(low, high, threads.max(1), cache.max(1))? });
+    }
+  }
+
+  // Among trials that take the same time, the first one (with fewer threads and less cache) is kept
+  let fastest: Trial = *trials.iter().min_by_key(|trial| trial.time).unwrap();
+  Ok(TuneResult {
+    profile: Profile { machine: machine_name(), threads: fastest.threads.max(1), cache: fastest.cache.max(1) },
+    low,
+    high,
+    trials,
+  })
+}
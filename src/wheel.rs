@@ -0,0 +1,196 @@
+use crate::bits;
+use crate::bits::Word;
+
+// The sieve uses a mod 30 wheel: each byte holds the 8 numbers 30 * k + r
+// with r coprime to 30, so the multiples of 2, 3 and 5 are never stored
+pub const WHEEL: usize = 30;
+pub const WHEEL_PRIMES: [u32; 3] = [2, 3, 5];
+pub const RESIDUES: [usize; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+
+// Distance from each residue to the next one
+const GAPS: [usize; 8] = [6, 4, 2, 4, 2, 4, 6, 2];
+
+// Bit of each residue modulo 30, or 8 if it isn't coprime to 30
+const BIT: [u8; 30] = {
+  let mut bit: [u8; 30] = [8; 30];
+  let mut i: usize = 0;
+  while i < 8 {
+    bit[RESIDUES[i]] = i as u8;
+    i += 1;
+  }
+  bit
+};
+
+// How many residues are lower than each number from 0 to 30
+const RESIDUES_BELOW: [u8; 31] = {
+  let mut below: [u8; 31] = [0; 31];
+  let mut r: usize = 1;
+  while r <= 30 {
+    below[r] = below[r - 1] + if BIT[r - 1] < 8 { 1 } else { 0 };
+    r += 1;
+  }
+  below
+};
+
+// Offsets of the multiples of each prime. For a prime p = 30 * a + RESIDUES[class] and
+// a multiple p * m with m = RESIDUES[i] (mod 30), MULTIPLE_BIT[class][i] is the bit of
+// p * m, and going to the next multiple p * (m + GAPS[i]) moves the byte by
+// a * GAPS[i] + MULTIPLE_STEP[class][i]
+const MULTIPLE_BIT: [[u8; 8]; 8] = {
+  let mut table: [[u8; 8]; 8] = [[0; 8]; 8];
+  let mut class: usize = 0;
+  while class < 8 {
+    let mut i: usize = 0;
+    while i < 8 {
+      table[class][i] = BIT[(RESIDUES[class] * RESIDUES[i]) % WHEEL];
+      i += 1;
+    }
+    class += 1;
+  }
+  table
+};
+
+const MULTIPLE_STEP: [[usize; 8]; 8] = {
+  let mut table: [[usize; 8]; 8] = [[0; 8]; 8];
+  let mut class: usize = 0;
+  while class < 8 {
+    let mut i: usize = 0;
+    while i < 8 {
+      let b: usize = RESIDUES[class];
+      table[class][i] = (b * (RESIDUES[i] + GAPS[i])) / WHEEL - (b * RESIDUES[i]) / WHEEL;
+      i += 1;
+    }
+    class += 1;
+  }
+  table
+};
+
+// Number of bytes (of 8 residues each) stored in each word of a sieve
+#[inline]
+pub fn bytes_per_word<W: Word>() -> usize {
+  W::BITS as usize / 8
+}
+
+// Number of words needed to store some bytes of a sieve
+#[inline]
+pub fn words<W: Word>(bytes: usize) -> usize {
+  bytes.div_ceil(bytes_per_word::<W>())
+}
+
+// Get the number stored in a bit of a word of a sieve starting at base
+#[inline]
+pub fn number<W: Word>(base: usize, word: usize, bit: u32) -> usize {
+  base + (word * bytes_per_word::<W>() + bit as usize / 8) * WHEEL + RESIDUES[bit as usize % 8]
+}
+
+// Get the bit of a number in its byte, from its offset to the start of the byte,
+// or None if it isn't coprime to 30 (so it isn't on the sieve)
+#[inline]
+pub fn bit_of(offset: usize) -> Option<u32> {
+  match BIT[offset] {
+    8 => None,
+    bit => Some(bit as u32),
+  }
+}
+
+// Count how many numbers of a byte are lower than offset
+#[inline]
+pub fn residues_below(offset: usize) -> u32 {
+  RESIDUES_BELOW[offset] as u32
+}
+
+// Unset a bit of a byte of a sieve
+#[inline]
+pub fn unset<W: Word>(sieve: &mut [W], byte: usize, bit: u8) {
+  let per_word: usize = bytes_per_word::<W>();
+  bits::unset_bit(&mut sieve[byte / per_word], (byte % per_word * 8) as u32 + bit as u32);
+}
+
+// Unset the bits of the numbers lower than offset in the first byte of a sieve
+#[inline]
+pub fn unset_below<W: Word>(sieve: &mut [W], offset: usize) {
+  bits::unset_first_bits(&mut sieve[0], RESIDUES_BELOW[offset] as u32);
+}
+
+// Unset the bits of the numbers greater than offset in a byte of a sieve,
+// and the bits of the bytes after it in the same word
+#[inline]
+pub fn unset_above<W: Word>(sieve: &mut [W], byte: usize, offset: usize) {
+  let per_word: usize = bytes_per_word::<W>();
+  let keep: u32 = (byte % per_word * 8) as u32 + RESIDUES_BELOW[offset + 1] as u32;
+  bits::unset_last_bits(&mut sieve[byte / per_word], W::BITS - keep);
+}
+
+// Next multiple p * m of a prime to cross off, with m coprime to 30
+pub struct Multiple {
+  // p / 30, which with the residue class of the prime gives the distance between multiples
+  quotient: u32,
+  class: u8,
+  // Index of m in RESIDUES
+  index: u8,
+  // Byte of the multiple in a sieve starting at 0
+  pub byte: usize,
+}
+
+impl Multiple {
+
+  // Get the first multiple p * m not greater than high, for m coprime to 30 and not lower than first
+  pub fn first(prime: usize, first: usize, high: usize) -> Option<Multiple> {
+
+    // Move to the first m coprime to 30
+    let mut m: usize = first;
+    while BIT[m % WHEEL] == 8 { m = m.checked_add(1)? }
+
+    let multiple: usize = prime.checked_mul(m).filter(|&multiple| multiple <= high)?;
+
+    Some(Multiple {
+      quotient: (prime / WHEEL) as u32,
+      class: BIT[prime % WHEEL],
+      index: BIT[m % WHEEL],
+      byte: multiple / WHEEL,
+    })
+  }
+
+  // Bit of the multiple in its byte
+  #[inline]
+  pub fn bit(&self) -> u8 {
+    MULTIPLE_BIT[self.class as usize][self.index as usize]
+  }
+
+  // Move to the next multiple
+  #[inline]
+  pub fn advance(&mut self) {
+    let (class, index): (usize, usize) = (self.class as usize, self.index as usize);
+    self.byte += self.quotient as usize * GAPS[index] + MULTIPLE_STEP[class][index];
+    self.index = ((index + 1) & 7) as u8;
+  }
+
+  // Unset the bits of the multiples in the first bytes of a sieve whose first byte is first_byte
+  // (the multiple can't be before it), stopping at the first one past them
+  #[inline]
+  pub fn cross_off<W: Word>(&mut self, sieve: &mut [W], first_byte: usize, bytes: usize) {
+
+    let class: usize = self.class as usize;
+    let a: usize = self.quotient as usize;
+    let mut byte: usize = self.byte - first_byte;
+    let mut i: usize = self.index as usize;
+
+    while byte < bytes {
+      unset(sieve, byte, MULTIPLE_BIT[class][i]);
+      byte += a * GAPS[i] + MULTIPLE_STEP[class][i];
+      i = (i + 1) & 7;
+    }
+
+    self.byte = byte + first_byte;
+    self.index = i as u8;
+  }
+}
+
+// Unset the bits of the multiples p * m of prime in a sieve starting at base (a multiple of 30),
+// for every m coprime to 30 not lower than first, and p * m not greater than high.
+// The bits above high in the last byte aren't used, so crossing them off too is harmless
+pub fn cross_off<W: Word>(sieve: &mut [W], base: usize, high: usize, prime: usize, first: usize) {
+  if let Some(mut multiple) = Multiple::first(prime, first, high) {
+    multiple.cross_off(sieve, base / WHEEL, (high - base) / WHEEL + 1);
+  }
+}